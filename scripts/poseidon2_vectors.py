@@ -0,0 +1,114 @@
+#!/usr/bin/env python3
+# Computes the Poseidon2 test vectors of src/poseidon/poseidon2.rs.
+#
+# This is a standalone implementation of the round constant generation (Grain LFSR) and of
+# the permutation of the reference implementation https://github.com/HorizenLabs/poseidon2
+# for the x^5 S-box, 8 full rounds and 56 partial rounds, and widths 2 and 3, for which
+# M_E = circ(2, 1, ..., 1) and M_I = J + diag(1, ..., 1, 2).
+#
+# It reproduces the published BN254 vector of the reference (poseidon2_instance_bn256)
+# before printing the vectors for the secp256k1 base field, for which none are published.
+#
+# Usage: python3 scripts/poseidon2_vectors.py
+
+BN254_R = 21888242871839275222246405745257275088548364400416034343698204186575808495617
+SECP256K1_P = 2**256 - 2**32 - 977
+
+NUM_FULL_ROUNDS = 8
+NUM_PARTIAL_ROUNDS = 56
+
+
+class Grain:
+    def __init__(self, field_bits, width):
+        self.bits = []
+        for value, num_bits in [
+            (1, 2),  # Prime field
+            (0, 4),  # x^alpha S-box
+            (field_bits, 12),
+            (width, 12),
+            (NUM_FULL_ROUNDS, 10),
+            (NUM_PARTIAL_ROUNDS, 10),
+            ((1 << 30) - 1, 30),
+        ]:
+            self.bits += [(value >> i) & 1 for i in reversed(range(num_bits))]
+
+        for _ in range(160):
+            self.step()
+
+    def step(self):
+        b = self.bits
+        new_bit = b[62] ^ b[51] ^ b[38] ^ b[23] ^ b[13] ^ b[0]
+        self.bits = b[1:] + [new_bit]
+        return new_bit
+
+    # Self-shrinking: a bit is output only if the preceding bit is one
+    def next_bit(self):
+        while True:
+            if self.step():
+                return self.step()
+            self.step()
+
+    # Rejection sampling of field elements, MSB first
+    def next_field_element(self, p):
+        while True:
+            x = 0
+            for _ in range(p.bit_length()):
+                x = (x << 1) | self.next_bit()
+            if x < p:
+                return x
+
+
+def round_keys(p, width):
+    grain = Grain(p.bit_length(), width)
+    half = NUM_FULL_ROUNDS // 2
+    keys = []
+    for r in range(NUM_FULL_ROUNDS + NUM_PARTIAL_ROUNDS):
+        is_partial = half <= r < half + NUM_PARTIAL_ROUNDS
+        keys.append([grain.next_field_element(p) for _ in range(1 if is_partial else width)])
+    return keys
+
+
+def permute(p, state):
+    width = len(state)
+    keys = round_keys(p, width)
+    half = NUM_FULL_ROUNDS // 2
+    diag = [1] * (width - 1) + [2]
+
+    def external(state):
+        s = sum(state)
+        return [(x + s) % p for x in state]
+
+    def internal(state):
+        s = sum(state)
+        return [(x * d + s) % p for x, d in zip(state, diag)]
+
+    def full_round(state, r):
+        return external([pow(x + k, 5, p) for x, k in zip(state, keys[r])])
+
+    state = external(state)
+    for r in range(half):
+        state = full_round(state, r)
+    for r in range(half, half + NUM_PARTIAL_ROUNDS):
+        state[0] = pow(state[0] + keys[r][0], 5, p)
+        state = internal(state)
+    for r in range(half + NUM_PARTIAL_ROUNDS, len(keys)):
+        state = full_round(state, r)
+
+    return state
+
+
+def to_hex(state):
+    return ["%064x" % x for x in state]
+
+
+if __name__ == "__main__":
+    assert to_hex(permute(BN254_R, [0, 1, 2])) == [
+        "0bb61d24daca55eebcb1929a82650f328134334da98ea4f847f760054f4a3033",
+        "303b6f7c86d043bfcbcc80214f26a30277a15d3f74ca654992defe7ff8d03570",
+        "1ed25194542b12eef8617361c3ba7c52e660b145994427cc86296242cf766ec8",
+    ], "the BN254 vector of the reference implementation is not reproduced"
+
+    for state in [[0, 1, 2], [0, 1]]:
+        print("secp256k1 base field, width %d, input %s:" % (len(state), state))
+        for x in to_hex(permute(SECP256K1_P, state)):
+            print("    " + x)
@@ -1,5 +1,6 @@
 use frontend::FieldGC;
 use frontend::Wire;
+use num_bigint::BigUint;
 use shockwave_plus::ark_ff::BigInteger;
 
 pub fn xor_64<F: FieldGC>(a: [Wire<F>; 64], b: [Wire<F>; 64]) -> [Wire<F>; 64] {
@@ -101,6 +102,70 @@ pub fn to_bits<F: FieldGC>(a: Wire<F>, field_bits: usize) -> Vec<Wire<F>> {
     bits
 }
 
+// Decomposes `a` into `num_bits` bits, LSB first.
+// If num_bits is less than the bit size of the field, this also range checks `a`
+// to be less than 2^num_bits. Otherwise, both `a` and `a + p` may fit in `num_bits` bits,
+// so the decomposition isn't unique and the caller must range check it,
+// e.g. with `is_less_than_const(&bits, &p)`.
+pub fn to_bits_le<F: FieldGC>(a: Wire<F>, num_bits: usize) -> Vec<Wire<F>> {
+    let cs = a.cs();
+
+    let bits = (0..num_bits)
+        .map(|_| cs.alloc_var(F::ZERO))
+        .collect::<Vec<Wire<F>>>();
+
+    if cs.is_witness_gen() {
+        let a_assigned = cs.wires[a.index];
+        let a_bits_native = a_assigned.into_bigint().to_bits_le();
+        for i in 0..num_bits {
+            cs.wires[bits[i].index] = F::from(a_bits_native[i]);
+        }
+    }
+
+    for a_i in &bits {
+        cs.assert_equal(*a_i * *a_i, *a_i, "to_bits_le: bit is not boolean");
+    }
+
+    let sum = from_bits(&bits);
+    cs.assert_equal(a, sum, "to_bits_le failed");
+
+    bits
+}
+
+// Returns 1 if the number represented by the bits (LSB first) is less than the constant `c`,
+// and 0 otherwise. The bits are assumed to be already constrained to be boolean.
+pub fn is_less_than_const<F: FieldGC>(bits: &[Wire<F>], c: &BigUint) -> Wire<F> {
+    let cs = bits[0].cs();
+    let one = cs.one();
+
+    // If `c` has more bits than `bits`, the number is always less than `c`
+    if c.bits() as usize > bits.len() {
+        return one;
+    }
+
+    let mut lt = cs.alloc_const(F::ZERO);
+    let mut eq = cs.one();
+
+    // Scan from the most significant bit
+    for (i, bit) in bits.iter().enumerate().rev() {
+        if c.bit(i as u64) {
+            // lt = lt + eq * (1 - bit)
+            lt = cs.deg_2_comb(
+                &[(eq, F::ONE)],
+                &[(*bit, -F::ONE), (one, F::ONE)],
+                &[(lt, F::ONE)],
+            );
+            // eq = eq * bit
+            eq = eq * *bit;
+        } else {
+            // eq = eq * (1 - bit)
+            eq = cs.deg_2_comb(&[(eq, F::ONE)], &[(*bit, -F::ONE), (one, F::ONE)], &[]);
+        }
+    }
+
+    lt
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,4 +225,61 @@ mod tests {
         cs.set_constraints(&synthesizer);
         assert!(cs.is_sat(&witness, &pub_input));
     }
+
+    #[test]
+    fn test_to_bits_le() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let val = cs.alloc_priv_input();
+            let out = to_bits_le(val, 64);
+
+            for out_i in out {
+                cs.expose_public(out_i);
+            }
+        };
+
+        let val = Fp::from(123456789u64);
+        let expected_bits = val.into_bigint().to_bits_le()[..64]
+            .iter()
+            .map(|b| Fp::from(*b))
+            .collect::<Vec<Fp>>();
+
+        let priv_input = [val];
+        let pub_input = expected_bits;
+
+        let mut cs = ConstraintSystem::new();
+        let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+
+        cs.set_constraints(&synthesizer);
+        assert!(cs.is_sat(&witness, &pub_input));
+    }
+
+    #[test]
+    fn test_is_less_than_const() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let c = BigUint::from(1000u32);
+            let val = cs.alloc_priv_input();
+            let bits = to_bits_le(val, 256);
+
+            let out = is_less_than_const(&bits, &c);
+            cs.expose_public(out);
+        };
+
+        let cases = [
+            (999u32, Fp::ONE),
+            (1000, Fp::ZERO),
+            (1001, Fp::ZERO),
+            (0, Fp::ONE),
+        ];
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        for (val, expected) in cases {
+            let priv_input = [Fp::from(val)];
+            let pub_input = [expected];
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
 }
@@ -1,11 +1,14 @@
-use super::AffinePoint;
+use super::{AffinePoint, CurveParams};
 use frontend::ConstraintSystem;
 use frontend::FieldGC;
 
 // Incomplete addition for short-Weierstrass curves.
 // We follow the specification from the halo2 book;
 // https://zcash.github.io/halo2/design/gadgets/sinsemilla.html?highlight=incomplete#incomplete-addition
-pub fn ec_add_incomplete<F: FieldGC>(p: AffinePoint<F>, q: AffinePoint<F>) -> AffinePoint<F> {
+pub fn ec_add_incomplete<F: FieldGC, C: CurveParams<F>>(
+    p: AffinePoint<F, C>,
+    q: AffinePoint<F, C>,
+) -> AffinePoint<F, C> {
     let cs = p.x.cs();
 
     let dx = p.x - q.x;
@@ -32,11 +35,15 @@ pub fn ec_add_incomplete<F: FieldGC>(p: AffinePoint<F>, q: AffinePoint<F>) -> Af
 // Complete addition for short-Weierstrass curves.
 // We follow the specification from the halo2 book.
 // https://zcash.github.io/halo2/design/gadgets/ecc/addition.html#complete-addition
-pub fn ec_add_complete<F: FieldGC>(
-    p: AffinePoint<F>,
-    q: AffinePoint<F>,
+// As there, p = q is handled by taking the slope of the tangent (3 * x^2 + a) / (2 * y)
+// instead of the slope of the chord, so the gadget is complete without a separate doubling.
+pub fn ec_add_complete<F: FieldGC, C: CurveParams<F>>(
+    p: AffinePoint<F, C>,
+    q: AffinePoint<F, C>,
     cs: &mut ConstraintSystem<F>,
-) -> AffinePoint<F> {
+) -> AffinePoint<F, C> {
+    C::assert_no_zero_coordinates();
+
     let is_x_equal = p.x.is_equal(q.x);
 
     let p_is_zero = p.x.is_zero();
@@ -48,19 +55,43 @@ pub fn ec_add_complete<F: FieldGC>(
 
     let zero = cs.alloc_const(F::ZERO);
 
-    let inc_add = ec_add_incomplete(p, q);
+    // The slope of the chord, which is zero if p.x = q.x
+    let chord = (p.y - q.y).div_or_zero(p.x - q.x);
+
+    // The slope of the tangent at p, which is only used if p.x = q.x.
+    // If p.y = 0 then p = -q, which is handled by is_sym.
+    let x_sq = p.x * p.x;
+    let three_x_sq = cs.mul_const(x_sq, F::from(3u32));
+    let tangent_num = cs.add_const(three_x_sq, C::a());
+    let two_y = cs.mul_const(p.y, F::from(2u32));
+    let tangent = tangent_num.div_or_zero(two_y);
+
+    let lambda = cs.if_then(is_x_equal, tangent).else_then(chord);
+
+    // out_x = (lambda * lambda) - p.x - q.x;
+    let add_x = cs.deg_2_comb(
+        &[(lambda, F::ONE)],
+        &[(lambda, F::ONE)],
+        &[(p.x, -F::ONE), (q.x, -F::ONE)],
+    );
+    // out_y = lambda * (p.x - out_x) - p.y;
+    let add_y = cs.deg_2_comb(
+        &[(lambda, F::ONE)],
+        &[(p.x, F::ONE), (add_x, -F::ONE)],
+        &[(p.y, -F::ONE)],
+    );
 
     let out_x = cs
         .if_then(is_out_zero, zero)
         .elif(p_is_zero, q.x, cs)
         .elif(q_is_zero, p.x, cs)
-        .else_then(inc_add.x);
+        .else_then(add_x);
 
     let out_y = cs
         .if_then(is_out_zero, zero)
         .elif(p_is_zero, q.y, cs)
         .elif(q_is_zero, p.y, cs)
-        .else_then(inc_add.y);
+        .else_then(add_y);
 
     AffinePoint::new(out_x, out_y)
 }
@@ -75,8 +106,12 @@ mod tests {
     type Fp = frontend::ark_secp256k1::Fq;
 
     use super::*;
+    use crate::Secp256k1;
 
-    fn add_incomplete_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>) {
+    fn add_incomplete_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
         let p_x = cs.alloc_priv_input();
         let p_y = cs.alloc_priv_input();
 
@@ -86,6 +121,9 @@ mod tests {
         let p = AffinePoint::<F>::new(p_x, p_y);
         let q = AffinePoint::<F>::new(q_x, q_y);
 
+        p.assert_on_curve(cs);
+        q.assert_on_curve(cs);
+
         let out = ec_add_incomplete(p, q);
 
         cs.expose_public(out.x);
@@ -113,7 +151,10 @@ mod tests {
         assert!(cs.is_sat(&witness, &pub_input));
     }
 
-    fn add_complete_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>) {
+    fn add_complete_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
         let p_x = cs.alloc_priv_input();
         let p_y = cs.alloc_priv_input();
 
@@ -123,10 +164,13 @@ mod tests {
         let p = AffinePoint::<F>::new(p_x, p_y);
         let q = AffinePoint::<F>::new(q_x, q_y);
 
+        p.assert_on_curve_or_identity(cs);
+        q.assert_on_curve_or_identity(cs);
+
         let out = ec_add_complete(p, q, cs);
 
         cs.expose_public(out.x);
-        cs.expose_public(out.x);
+        cs.expose_public(out.y);
     }
 
     #[test]
@@ -144,6 +188,8 @@ mod tests {
             (p_nonzero, zero),
             (p_nonzero, -q_nonzero),
             (p_nonzero, q_nonzero),
+            (p_nonzero, -p_nonzero),
+            (p_nonzero, p_nonzero),
         ];
 
         let mut cs = ConstraintSystem::<Fp>::new();
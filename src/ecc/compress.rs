@@ -0,0 +1,178 @@
+use super::{AffinePoint, CurveParams};
+use crate::bitops::{is_less_than_const, to_bits_le};
+use frontend::ark_ff::BigInteger;
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+use num_bigint::BigUint;
+
+// Returns the least significant bit of the canonical representation of `y`.
+// The bit decomposition must be less than the modulus,
+// otherwise both y and y + p would be accepted and the parity could be flipped.
+pub(crate) fn parity<F: FieldGC>(y: Wire<F>, cs: &mut ConstraintSystem<F>) -> Wire<F> {
+    let one = cs.one();
+    let p: BigUint = F::MODULUS.into();
+
+    let y_bits = to_bits_le(y, F::MODULUS_BIT_SIZE as usize);
+    cs.assert_equal(
+        is_less_than_const(&y_bits, &p),
+        one,
+        "y is not in canonical form",
+    );
+
+    y_bits[0]
+}
+
+// Compresses the point into its x coordinate and the parity of its y coordinate,
+// as in the SEC1 encoding (prefix = 2 + is_odd).
+pub fn ec_compress<F: FieldGC, C: CurveParams<F>>(
+    p: AffinePoint<F, C>,
+    cs: &mut ConstraintSystem<F>,
+) -> (Wire<F>, Wire<F>) {
+    let is_odd = parity(p.y, cs);
+    (p.x, is_odd)
+}
+
+// Decompresses the point from its x coordinate and the parity of its y coordinate.
+// The square root y is witnessed, and constrained by the curve equation and the parity.
+// `is_odd` is assumed to be already constrained to be a bit.
+pub fn ec_decompress<F: FieldGC, C: CurveParams<F>>(
+    x: Wire<F>,
+    is_odd: Wire<F>,
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F, C> {
+    let y = cs.alloc_var(F::ZERO);
+
+    if cs.is_witness_gen() {
+        let x_assigned = cs.wires[x.index];
+        let rhs = x_assigned * x_assigned * x_assigned + C::a() * x_assigned + C::b();
+
+        if let Some(y_assigned) = rhs.sqrt() {
+            let is_odd_assigned = cs.wires[is_odd.index] == F::ONE;
+            cs.wires[y.index] = if y_assigned.into_bigint().is_odd() == is_odd_assigned {
+                y_assigned
+            } else {
+                -y_assigned
+            };
+        }
+    }
+
+    let p = AffinePoint::<F, C>::new(x, y);
+    p.assert_on_curve(cs);
+
+    let y_is_odd = parity(y, cs);
+    cs.assert_equal(y_is_odd, is_odd, "parity of y mismatch");
+
+    p
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ec::{AffineRepr, CurveGroup};
+    use frontend::ark_ff::{Field, PrimeField};
+    use frontend::ark_secp256k1::Affine as Secp256k1Affine;
+    use frontend::ark_secp256k1::Fr;
+
+    use super::*;
+    use crate::test_utils::is_rejected;
+    use crate::Secp256k1;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    const ASSERTIONS: [&str; 6] = [
+        "is_odd is not a bit",
+        "point is not on the curve",
+        "y is not in canonical form",
+        "parity of y mismatch",
+        "x mismatch",
+        "parity mismatch",
+    ];
+
+    // SEC1 compressed encoding: 0x02 or 0x03 followed by the big-endian x coordinate
+    fn compress_native(p: Secp256k1Affine) -> [u8; 33] {
+        let mut out = [0u8; 33];
+        out[0] = if p.y.into_bigint().is_odd() { 3 } else { 2 };
+        out[1..].copy_from_slice(&p.x.into_bigint().to_bytes_be());
+        out
+    }
+
+    fn decompress_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let x = cs.alloc_priv_input();
+        let is_odd = cs.alloc_priv_input();
+        cs.assert_equal(is_odd * is_odd, is_odd, "is_odd is not a bit");
+
+        let p = ec_decompress::<F, Secp256k1>(x, is_odd, cs);
+
+        // Compressing the point again must give back the same encoding
+        let (x_out, is_odd_out) = ec_compress(p, cs);
+        cs.assert_equal(x_out, x, "x mismatch");
+        cs.assert_equal(is_odd_out, is_odd, "parity mismatch");
+
+        cs.expose_public(p.x);
+        cs.expose_public(p.y);
+    }
+
+    #[test]
+    fn test_decompress() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| decompress_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        let p_even_or_odd = (Secp256k1Affine::generator() * Fr::from(3u32)).into_affine();
+
+        for p in [p_even_or_odd, -p_even_or_odd] {
+            let compressed = compress_native(p);
+            let x = Fp::from(BigUint::from_bytes_be(&compressed[1..]));
+            let is_odd = Fp::from(compressed[0] == 3);
+
+            let priv_input = [x, is_odd];
+            let pub_input = [p.x, p.y];
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
+
+    #[test]
+    fn test_decompress_wrong_parity() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| decompress_circuit(cs);
+
+        let p = (Secp256k1Affine::generator() * Fr::from(3u32)).into_affine();
+        let compressed = compress_native(p);
+        let x = Fp::from(BigUint::from_bytes_be(&compressed[1..]));
+        let is_odd = Fp::from(compressed[0] == 3);
+
+        // The flipped parity bit decompresses to -p instead of p
+        let flipped = Fp::from(1u32) - is_odd;
+        assert!(is_rejected(
+            synthesizer,
+            &[p.x, p.y],
+            &[x, flipped],
+            &ASSERTIONS
+        ));
+    }
+
+    #[test]
+    fn test_decompress_not_on_curve() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| decompress_circuit(cs);
+
+        // The smallest x > 0 for which x^3 + 7 has no square root
+        let mut x = Fp::from(1u32);
+        while (x * x * x + Fp::from(7u32)).sqrt().is_some() {
+            x += Fp::from(1u32);
+        }
+
+        let zero = Fp::from(0u32);
+        for is_odd in [zero, Fp::from(1u32)] {
+            assert!(is_rejected(
+                synthesizer,
+                &[x, zero],
+                &[x, is_odd],
+                &ASSERTIONS
+            ));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use ark_ec::AffineRepr;
+use frontend::ark_secp256k1::{Affine as Secp256k1Affine, Fq, Fr};
+use frontend::FieldGC;
+use num_bigint::BigUint;
+
+// Parameters of a short-Weierstrass curve y^2 = x^3 + a * x + b over the field F.
+pub trait CurveParams<F: FieldGC>: Copy + Clone {
+    fn a() -> F;
+    fn b() -> F;
+    fn generator() -> (F, F);
+
+    // The complete gadgets assume that no point on the curve has a zero coordinate:
+    // - b is a non-residue, so no point has x = 0.
+    //   The identity is encoded as (0, 0) and detected by x = 0 alone.
+    // - x^3 + a * x + b has no root, so no point has y = 0.
+    //   Doubling then never divides by zero, and p = -p only for the identity.
+    fn assert_no_zero_coordinates() {
+        assert!(Self::b().legendre().is_qnr(), "b is a residue");
+        assert!(
+            !has_root(Self::a(), Self::b()),
+            "x^3 + a * x + b has a root"
+        );
+    }
+}
+
+// Polynomials are represented by their coefficients, lowest degree first,
+// without trailing zeros
+fn trim<F: FieldGC>(a: &mut Vec<F>) {
+    while a.last() == Some(&F::ZERO) {
+        a.pop();
+    }
+}
+
+fn poly_mul<F: FieldGC>(a: &[F], b: &[F]) -> Vec<F> {
+    let mut out = vec![F::ZERO; (a.len() + b.len()).saturating_sub(1)];
+    for (i, a_i) in a.iter().enumerate() {
+        for (j, b_j) in b.iter().enumerate() {
+            out[i + j] += *a_i * b_j;
+        }
+    }
+    trim(&mut out);
+    out
+}
+
+// The remainder of a divided by a non-zero b
+fn poly_rem<F: FieldGC>(mut a: Vec<F>, b: &[F]) -> Vec<F> {
+    let lead_inv = b.last().unwrap().inverse().unwrap();
+    while a.len() >= b.len() {
+        let c = *a.last().unwrap() * lead_inv;
+        let shift = a.len() - b.len();
+        for (i, b_i) in b.iter().enumerate() {
+            a[shift + i] -= c * b_i;
+        }
+        trim(&mut a);
+    }
+    a
+}
+
+// Whether x^3 + a * x + b has a root in F, i.e. gcd(x^3 + a * x + b, x^p - x) != 1
+fn has_root<F: FieldGC>(a: F, b: F) -> bool {
+    let mut f = vec![b, a, F::ZERO, F::ONE];
+    trim(&mut f);
+
+    // x^p mod f, by square-and-multiply
+    let modulus: BigUint = F::MODULUS.into();
+    let mut x_pow = vec![F::ONE];
+    for i in (0..modulus.bits()).rev() {
+        x_pow = poly_rem(poly_mul(&x_pow, &x_pow), &f);
+        if modulus.bit(i) {
+            x_pow = poly_rem(poly_mul(&x_pow, &[F::ZERO, F::ONE]), &f);
+        }
+    }
+
+    // x^p - x mod f
+    x_pow.resize(2.max(x_pow.len()), F::ZERO);
+    x_pow[1] -= F::ONE;
+    trim(&mut x_pow);
+
+    let (mut g, mut r) = (f, x_pow);
+    while !r.is_empty() {
+        let next = poly_rem(g, &r);
+        g = r;
+        r = next;
+    }
+
+    // A non-constant gcd
+    g.len() > 1
+}
+
+// secp256k1: y^2 = x^3 + 7 over the secp256k1 base field
+#[derive(Copy, Clone)]
+pub struct Secp256k1;
+
+impl CurveParams<Fq> for Secp256k1 {
+    fn a() -> Fq {
+        Fq::from(0u32)
+    }
+
+    fn b() -> Fq {
+        Fq::from(7u32)
+    }
+
+    fn generator() -> (Fq, Fq) {
+        let g = Secp256k1Affine::generator();
+        (g.x, g.y)
+    }
+}
+
+// secq256k1: y^2 = x^3 + 7 over the secp256k1 scalar field
+#[derive(Copy, Clone)]
+pub struct Secq256k1;
+
+impl CurveParams<Fr> for Secq256k1 {
+    fn a() -> Fr {
+        Fr::from(0u32)
+    }
+
+    fn b() -> Fr {
+        Fr::from(7u32)
+    }
+
+    fn generator() -> (Fr, Fr) {
+        let x = BigUint::parse_bytes(
+            b"53718550993811904772965658690407829053653678808745171666022356150019200052646",
+            10,
+        )
+        .unwrap();
+        let y = BigUint::parse_bytes(
+            b"28941648020349172432234515805717979317553499307621291159490218670604692907903",
+            10,
+        )
+        .unwrap();
+
+        (Fr::from(x), Fr::from(y))
+    }
+}
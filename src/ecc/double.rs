@@ -1,12 +1,18 @@
-use super::AffinePoint;
+use super::{AffinePoint, CurveParams};
 use frontend::ConstraintSystem;
 use frontend::FieldGC;
 
 // Doubling for short-Weierstrass curves
-pub fn ec_double<F: FieldGC>(p: AffinePoint<F>, cs: &mut ConstraintSystem<F>) -> AffinePoint<F> {
-    // lambda = (3 * x^2) / (2 * y)
-    let lambda =
-        (cs.alloc_const(F::from(3u32)) * (p.x * p.x)) / (cs.alloc_const(F::from(2u32)) * p.y);
+pub fn ec_double<F: FieldGC, C: CurveParams<F>>(
+    p: AffinePoint<F, C>,
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F, C> {
+    // lambda = (3 * x^2 + a) / (2 * y)
+    let mut numerator = cs.alloc_const(F::from(3u32)) * (p.x * p.x);
+    if C::a() != F::ZERO {
+        numerator = cs.add_const(numerator, C::a());
+    }
+    let lambda = numerator / (cs.alloc_const(F::from(2u32)) * p.y);
 
     // x = lambda^2 - 2 * x
     let out_x = (lambda * lambda) - (p.x * cs.alloc_const(F::from(2u32)));
@@ -16,6 +22,30 @@ pub fn ec_double<F: FieldGC>(p: AffinePoint<F>, cs: &mut ConstraintSystem<F>) ->
     AffinePoint::new(out_x, out_y)
 }
 
+// Complete doubling for short-Weierstrass curves.
+// The identity is encoded as (0, 0), as in `ec_add_complete`.
+// Doubling the identity would divide by zero, so we double a dummy point instead
+// and select the identity as the output.
+pub fn ec_double_complete<F: FieldGC, C: CurveParams<F>>(
+    p: AffinePoint<F, C>,
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F, C> {
+    C::assert_no_zero_coordinates();
+
+    let p_is_zero = p.x.is_zero();
+
+    let zero = cs.alloc_const(F::ZERO);
+    let one = cs.one();
+
+    let safe_y = cs.if_then(p_is_zero, one).else_then(p.y);
+    let doubled = ec_double(AffinePoint::<F, C>::new(p.x, safe_y), cs);
+
+    let out_x = cs.if_then(p_is_zero, zero).else_then(doubled.x);
+    let out_y = cs.if_then(p_is_zero, zero).else_then(doubled.y);
+
+    AffinePoint::new(out_x, out_y)
+}
+
 #[cfg(test)]
 mod tests {
     use frontend::ark_secp256k1::Fr;
@@ -35,6 +65,8 @@ mod tests {
 
             let p = AffinePoint::<F>::new(p_x, p_y);
 
+            p.assert_on_curve(cs);
+
             let out = ec_double(p, cs);
 
             cs.expose_public(out.x);
@@ -53,4 +85,37 @@ mod tests {
         cs.set_constraints(&synthesizer);
         assert!(cs.is_sat(&witness, &pub_input));
     }
+
+    #[test]
+    fn test_ec_double_complete() {
+        let synthesizer = |cs: &mut ConstraintSystem<F>| {
+            let p_x = cs.alloc_priv_input();
+            let p_y = cs.alloc_priv_input();
+
+            let p = AffinePoint::<F>::new(p_x, p_y);
+
+            p.assert_on_curve_or_identity(cs);
+
+            let out = ec_double_complete(p, cs);
+
+            cs.expose_public(out.x);
+            cs.expose_public(out.y);
+        };
+
+        let zero = Secp256k1Affine::identity();
+        let p_nonzero = (Secp256k1Affine::generator() * Fr::from(3)).into_affine();
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        for p in [zero, p_nonzero] {
+            let p_double = (p + p).into_affine();
+
+            let pub_input = [p_double.x, p_double.y];
+            let priv_input = [p.x, p.y];
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
 }
@@ -0,0 +1,224 @@
+use super::{
+    add::ec_add_complete, mul::ec_mul, secp256k1_order, to_field, AffinePoint, CurveParams,
+    Secp256k1,
+};
+use crate::bitops::{is_less_than_const, to_bits_le};
+use ark_ec::{AffineRepr, CurveGroup};
+use frontend::ark_ff::Field;
+use frontend::ark_secp256k1::{Affine as Secp256k1Affine, Fq, Fr};
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+use num_bigint::BigUint;
+
+// Verifies an ECDSA signature (r, s) of `msghash` under `pub_key` over secp256k1.
+// Computing u1 = m * s^-1 and u2 = r * s^-1 requires arithmetic modulo the group order,
+// which is non-native to the circuit. Instead, we witness the nonce point
+// R = s^-1 * (m * G + r * Q) and check that s * R = m * G + r * Q,
+// which only multiplies points by the integers r, s and m.
+// The signature is valid iff R.x mod n = r.
+pub fn ecdsa_verify<F: FieldGC>(
+    r: Wire<F>,
+    s: Wire<F>,
+    msghash: Wire<F>,
+    pub_key: AffinePoint<F>,
+    cs: &mut ConstraintSystem<F>,
+) where
+    Secp256k1: CurveParams<F>,
+{
+    let zero = cs.alloc_const(F::ZERO);
+    let one = cs.one();
+
+    let n = secp256k1_order();
+    let p: BigUint = F::MODULUS.into();
+
+    // Witness the nonce point R
+    let R = AffinePoint::<F>::new(cs.alloc_var(F::ZERO), cs.alloc_var(F::ZERO));
+    if cs.is_witness_gen() {
+        let r_native = to_field::<F, Fr>(cs.wires[r.index]);
+        let s_native = to_field::<F, Fr>(cs.wires[s.index]);
+        let m_native = to_field::<F, Fr>(cs.wires[msghash.index]);
+        let q = Secp256k1Affine::new_unchecked(
+            to_field::<F, Fq>(cs.wires[pub_key.x.index]),
+            to_field::<F, Fq>(cs.wires[pub_key.y.index]),
+        );
+
+        let s_inv = s_native.inverse().unwrap_or(Fr::ZERO);
+        let R_native = (Secp256k1Affine::generator() * (m_native * s_inv) + q * (r_native * s_inv))
+            .into_affine();
+
+        cs.wires[R.x.index] = to_field(R_native.x);
+        cs.wires[R.y.index] = to_field(R_native.y);
+    }
+
+    R.assert_on_curve(cs);
+
+    let r_bits = to_bits_le(r, 256);
+    let s_bits = to_bits_le(s, 256);
+    let m_bits = to_bits_le(msghash, 256);
+
+    // 0 < r < n and 0 < s < n
+    cs.assert_equal(is_less_than_const(&r_bits, &n), one, "r is not less than n");
+    cs.assert_equal(is_less_than_const(&s_bits, &n), one, "s is not less than n");
+    cs.assert_equal(r.is_zero(), zero, "r is zero");
+    cs.assert_equal(s.is_zero(), zero, "s is zero");
+
+    // The decomposition of the message hash must be canonical
+    cs.assert_equal(
+        is_less_than_const(&m_bits, &p),
+        one,
+        "msghash is not less than p",
+    );
+
+    // s * R = m * G + r * Q
+    let G = AffinePoint::<F>::generator(cs);
+
+    let lhs = ec_mul(R, &s_bits, cs);
+    let mG = ec_mul(G, &m_bits, cs);
+    let rQ = ec_mul(pub_key, &r_bits, cs);
+    let rhs = ec_add_complete(mG, rQ, cs);
+
+    cs.assert_equal(lhs.x, rhs.x, "s * R != m * G + r * Q");
+    cs.assert_equal(lhs.y, rhs.y, "s * R != m * G + r * Q");
+
+    // Non-native reduction of R.x modulo n.
+    // Since n < p < 2n, R.x mod n = r iff R.x = r or R.x = r + n,
+    // where the latter is only possible if r + n < p, i.e. r < p - n.
+    let wraps = cs.alloc_var(F::ZERO);
+    if cs.is_witness_gen() {
+        cs.wires[wraps.index] = F::from(cs.wires[R.x.index] != cs.wires[r.index]);
+    }
+    cs.assert_equal(wraps * wraps, wraps, "wraps is not a bit");
+
+    let r_lt_p_minus_n = is_less_than_const(&r_bits, &(&p - &n));
+    cs.assert_equal(
+        wraps * (one - r_lt_p_minus_n),
+        zero,
+        "r + n overflows the base field",
+    );
+
+    let reduced = r + cs.mul_const(wraps, F::from(n));
+    cs.assert_equal(R.x, reduced, "R.x mod n != r");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::is_rejected;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    // The messages of the assertions of ecdsa_verify
+    const ASSERTIONS: [&str; 9] = [
+        "r is not less than n",
+        "s is not less than n",
+        "r is zero",
+        "s is zero",
+        "msghash is not less than p",
+        "s * R != m * G + r * Q",
+        "wraps is not a bit",
+        "r + n overflows the base field",
+        "R.x mod n != r",
+    ];
+
+    fn ecdsa_verify_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let r = cs.alloc_priv_input();
+        let s = cs.alloc_priv_input();
+        let msghash = cs.alloc_priv_input();
+
+        let q_x = cs.alloc_priv_input();
+        let q_y = cs.alloc_priv_input();
+        let pub_key = AffinePoint::new(q_x, q_y);
+
+        ecdsa_verify(r, s, msghash, pub_key, cs);
+
+        cs.expose_public(pub_key.x);
+        cs.expose_public(pub_key.y);
+    }
+
+    // Signs the message hash with the given private key and nonce
+    fn sign(priv_key: Fr, k: Fr, msghash: Fr) -> (Fr, Fr) {
+        let R = (Secp256k1Affine::generator() * k).into_affine();
+        let r = to_field::<Fp, Fr>(R.x);
+        let s = k.inverse().unwrap() * (msghash + r * priv_key);
+
+        (r, s)
+    }
+
+    fn to_inputs(r: Fr, s: Fr, msghash: Fr, pub_key: Secp256k1Affine) -> ([Fp; 5], [Fp; 2]) {
+        let priv_input = [
+            to_field::<Fr, Fp>(r),
+            to_field::<Fr, Fp>(s),
+            to_field::<Fr, Fp>(msghash),
+            pub_key.x,
+            pub_key.y,
+        ];
+        let pub_input = [pub_key.x, pub_key.y];
+
+        (priv_input, pub_input)
+    }
+
+    #[test]
+    fn test_ecdsa_verify() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| ecdsa_verify_circuit(cs);
+
+        let priv_key = Fr::from(123456789u64);
+        let pub_key = (Secp256k1Affine::generator() * priv_key).into_affine();
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+
+        let cases = [
+            (Fr::from(42u64), Fr::from(987654321u64)),
+            (-Fr::from(3u64), Fr::from(1122334455u64)),
+        ];
+
+        for (msghash, k) in cases {
+            let (r, s) = sign(priv_key, k, msghash);
+            let (priv_input, pub_input) = to_inputs(r, s, msghash, pub_key);
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
+
+    #[test]
+    fn test_ecdsa_verify_rejects() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| ecdsa_verify_circuit(cs);
+
+        let priv_key = Fr::from(123456789u64);
+        let pub_key = (Secp256k1Affine::generator() * priv_key).into_affine();
+        let other_key = (Secp256k1Affine::generator() * Fr::from(987654321u64)).into_affine();
+
+        let msghash = Fr::from(42u64);
+        let (r, s) = sign(priv_key, Fr::from(987654321u64), msghash);
+        let one = Fr::from(1u64);
+
+        let (priv_input, pub_input) = to_inputs(r, s, msghash, pub_key);
+        assert!(!is_rejected(
+            synthesizer,
+            &pub_input,
+            &priv_input,
+            &ASSERTIONS
+        ));
+
+        let cases = [
+            ("wrong r", to_inputs(r + one, s, msghash, pub_key)),
+            ("wrong s", to_inputs(r, s + one, msghash, pub_key)),
+            ("wrong message", to_inputs(r, s, msghash + one, pub_key)),
+            ("wrong key", to_inputs(r, s, msghash, other_key)),
+        ];
+
+        for (name, (priv_input, pub_input)) in cases {
+            assert!(
+                is_rejected(synthesizer, &pub_input, &priv_input, &ASSERTIONS),
+                "{} accepted",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use super::{add::ec_add_complete, mul::ec_mul, AffinePoint, CurveParams};
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+
+// Recovers the public key from an ECDSA signature in the "efficient ECDSA" form.
+// https://personaelabs.org/posts/efficient-ecdsa-1/
+// The points T = r^-1 * R and U = -(r^-1 * m) * G are computed outside of the circuit,
+// so the circuit only proves Q = s * T + U and doesn't perform any arithmetic
+// modulo the group order. `s_bits` are interpreted as LSB first.
+pub fn efficient_ecdsa<F: FieldGC, C: CurveParams<F>>(
+    T: AffinePoint<F, C>,
+    U: AffinePoint<F, C>,
+    s_bits: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F, C> {
+    let sT = ec_mul(T, s_bits, cs);
+    ec_add_complete(sT, U, cs)
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ec::{AffineRepr, CurveGroup};
+    use frontend::ark_ff::{BigInteger, Field, PrimeField};
+    use frontend::ark_secp256k1::Affine as Secp256k1Affine;
+    use frontend::ark_secp256k1::Fr;
+
+    use super::*;
+    use crate::ecc::to_field;
+    use crate::Secp256k1;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    fn efficient_ecdsa_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let t_x = cs.alloc_priv_input();
+        let t_y = cs.alloc_priv_input();
+        let u_x = cs.alloc_priv_input();
+        let u_y = cs.alloc_priv_input();
+        let s_bits = cs.alloc_priv_inputs(256);
+
+        let T = AffinePoint::<F>::new(t_x, t_y);
+        let U = AffinePoint::<F>::new(u_x, u_y);
+        T.assert_on_curve(cs);
+        U.assert_on_curve(cs);
+
+        let pub_key = efficient_ecdsa(T, U, &s_bits, cs);
+
+        cs.expose_public(pub_key.x);
+        cs.expose_public(pub_key.y);
+    }
+
+    #[test]
+    fn test_efficient_ecdsa() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| efficient_ecdsa_circuit(cs);
+
+        let g = Secp256k1Affine::generator();
+        let priv_key = Fr::from(123456789u64);
+        let pub_key = (g * priv_key).into_affine();
+
+        // Sign the message hash
+        let msghash = Fr::from(42u64);
+        let k = Fr::from(987654321u64);
+        let R = (g * k).into_affine();
+        let r = to_field::<Fp, Fr>(R.x);
+        let s = k.inverse().unwrap() * (msghash + r * priv_key);
+
+        // Compute T and U
+        let r_inv = r.inverse().unwrap();
+        let T = (R * r_inv).into_affine();
+        let U = (g * -(r_inv * msghash)).into_affine();
+
+        let s_bits = s
+            .into_bigint()
+            .to_bits_le()
+            .iter()
+            .map(|b| Fp::from(*b))
+            .collect::<Vec<Fp>>();
+
+        let pub_input = vec![pub_key.x, pub_key.y];
+        let mut priv_input = vec![T.x, T.y, U.x, U.y];
+        priv_input.extend_from_slice(&s_bits);
+
+        let mut cs = ConstraintSystem::new();
+        let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+        assert!(cs.is_sat(&witness, &pub_input));
+    }
+}
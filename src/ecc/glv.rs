@@ -0,0 +1,417 @@
+use super::double::ec_double_complete;
+use super::mul::select_wire;
+use super::{add::ec_add_complete, from_hex, secp256k1_order, AffinePoint, CurveParams, Secp256k1};
+use crate::bitops::{from_bits, to_bits_le};
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+use num_bigint::{BigInt, BigUint, Sign};
+
+// Number of bits of each half of the decomposed scalar
+const GLV_BITS: usize = 129;
+// Number of bits of the quotient t in k1 + lambda * k2 = k + t * n
+const QUOTIENT_BITS: usize = 130;
+// The carries of the limb-wise check are range checked to CARRY_BITS bits after adding 2^(CARRY_BITS - 1)
+const CARRY_BITS: usize = 70;
+const LIMB_BITS: usize = 64;
+
+// phi(x, y) = (beta * x, y) = lambda * (x, y)
+const BETA: &str = "7ae96a2b657c07106e64479eac3434e99cf0497512f58995c1396c28719501ee";
+const LAMBDA: &str = "5363ad4cc05c30e0a5261c028812645a122e22ea20816678df02967c1b23bd72";
+
+// Short basis of the lattice {(a, b) : a + b * lambda = 0 mod n}
+const A1: &str = "3086d221a7d46bcde86c90e49284eb15";
+const MINUS_B1: &str = "e4437ed6010e88286f547fa90abfe4c3";
+const A2: &str = "114ca50f7a8e2f3f657c1108d9d44cfd8";
+const B2: &str = "3086d221a7d46bcde86c90e49284eb15";
+
+fn to_field_signed<F: FieldGC>(x: &BigInt) -> F {
+    let magnitude = F::from(x.magnitude().clone());
+    if x.sign() == Sign::Minus {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+fn to_limbs(x: &BigUint, num_limbs: usize) -> Vec<BigUint> {
+    let mask = (BigUint::from(1u32) << LIMB_BITS) - 1u32;
+    (0..num_limbs)
+        .map(|i| (x >> (i * LIMB_BITS)) & &mask)
+        .collect()
+}
+
+// Decomposes the scalar k into (k1, k2) such that k = k1 + lambda * k2 mod n,
+// where |k1| and |k2| are about 128 bits.
+// We follow Section 3.5 of the Guide to Elliptic Curve Cryptography.
+pub fn glv_decompose(k: &BigUint) -> (BigInt, BigInt) {
+    let n = BigInt::from(secp256k1_order());
+    let k = BigInt::from(k.clone());
+
+    let a1 = BigInt::from(from_hex(A1));
+    let minus_b1 = BigInt::from(from_hex(MINUS_B1));
+    let a2 = BigInt::from(from_hex(A2));
+    let b2 = BigInt::from(from_hex(B2));
+
+    // c1 = round(b2 * k / n), c2 = round(-b1 * k / n)
+    let two = BigInt::from(2u32);
+    let c1 = (&b2 * &k * &two + &n) / (&n * &two);
+    let c2 = (&minus_b1 * &k * &two + &n) / (&n * &two);
+
+    let k1 = &k - &c1 * &a1 - &c2 * &a2;
+    let k2 = &c1 * &minus_b1 - &c2 * &b2;
+
+    (k1, k2)
+}
+
+// GLV scalar multiplication for secp256k1.
+// The scalar is decomposed as k = k1 + lambda * k2 mod n, so that
+// k * p = k1 * p + k2 * phi(p) can be computed with a joint double-and-add
+// over the ~128-bit halves, which halves the number of doublings.
+// The decomposition is witnessed and checked with a limb-wise integer identity
+// s1 * |k1| + s2 * lambda * |k2| - k - st * |t| * n = 0, where s1, s2 and st are the signs.
+// `k_bits` are interpreted as LSB first.
+pub fn ec_mul_glv<F: FieldGC>(
+    p: AffinePoint<F>,
+    k_bits: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F>
+where
+    Secp256k1: CurveParams<F>,
+{
+    let n = secp256k1_order();
+    let lambda = from_hex(LAMBDA);
+    let beta = F::from(from_hex(BETA));
+    let one = cs.one();
+    let zero = cs.alloc_const(F::ZERO);
+
+    // ########################################
+    // Witness the decomposition
+    // ########################################
+
+    let k1_neg = cs.alloc_var(F::ZERO);
+    let k2_neg = cs.alloc_var(F::ZERO);
+    let t_neg = cs.alloc_var(F::ZERO);
+    let k1_abs = cs.alloc_var(F::ZERO);
+    let k2_abs = cs.alloc_var(F::ZERO);
+    let t_abs = cs.alloc_var(F::ZERO);
+
+    if cs.is_witness_gen() {
+        let k = k_bits.iter().rev().fold(BigUint::from(0u32), |acc, b| {
+            let b: BigUint = cs.wires[b.index].into();
+            (acc << 1) + b
+        });
+
+        let (k1, k2) = glv_decompose(&k);
+        let t =
+            (&k1 + BigInt::from(lambda.clone()) * &k2 - BigInt::from(k)) / BigInt::from(n.clone());
+
+        cs.wires[k1_neg.index] = F::from(k1.sign() == Sign::Minus);
+        cs.wires[k2_neg.index] = F::from(k2.sign() == Sign::Minus);
+        cs.wires[t_neg.index] = F::from(t.sign() == Sign::Minus);
+        cs.wires[k1_abs.index] = F::from(k1.magnitude().clone());
+        cs.wires[k2_abs.index] = F::from(k2.magnitude().clone());
+        cs.wires[t_abs.index] = F::from(t.magnitude().clone());
+    }
+
+    for neg in [k1_neg, k2_neg, t_neg] {
+        cs.assert_equal(neg * neg, neg, "sign is not a bit");
+    }
+
+    let k1_bits = to_bits_le(k1_abs, GLV_BITS);
+    let k2_bits = to_bits_le(k2_abs, GLV_BITS);
+    let t_bits = to_bits_le(t_abs, QUOTIENT_BITS);
+
+    // ########################################
+    // Check k1 + lambda * k2 = k + t * n over the integers, limb by limb
+    // ########################################
+
+    let k1_limbs = k1_bits
+        .chunks(LIMB_BITS)
+        .map(from_bits)
+        .collect::<Vec<Wire<F>>>();
+    let k2_limbs = k2_bits
+        .chunks(LIMB_BITS)
+        .map(from_bits)
+        .collect::<Vec<Wire<F>>>();
+    let t_limbs = t_bits
+        .chunks(LIMB_BITS)
+        .map(from_bits)
+        .collect::<Vec<Wire<F>>>();
+    let k_limbs = k_bits
+        .chunks(LIMB_BITS)
+        .map(from_bits)
+        .collect::<Vec<Wire<F>>>();
+
+    let lambda_limbs = to_limbs(&lambda, 4);
+    let n_limbs = to_limbs(&n, 4);
+
+    let num_positions = (lambda_limbs.len() + k2_limbs.len() - 1)
+        .max(n_limbs.len() + t_limbs.len() - 1)
+        .max(k_limbs.len());
+
+    // The linear combinations of each limb of the terms
+    let mut k1_terms = vec![vec![]; num_positions];
+    let mut lambda_k2_terms = vec![vec![]; num_positions];
+    let mut t_n_terms = vec![vec![]; num_positions];
+
+    for (i, limb) in k1_limbs.iter().enumerate() {
+        k1_terms[i].push((*limb, F::ONE));
+    }
+    for (i, lambda_i) in lambda_limbs.iter().enumerate() {
+        for (j, limb) in k2_limbs.iter().enumerate() {
+            lambda_k2_terms[i + j].push((*limb, F::from(lambda_i.clone())));
+        }
+    }
+    for (i, n_i) in n_limbs.iter().enumerate() {
+        for (j, limb) in t_limbs.iter().enumerate() {
+            t_n_terms[i + j].push((*limb, F::from(n_i.clone())));
+        }
+    }
+
+    // Witness the carries
+    let carries = (0..(num_positions - 1))
+        .map(|_| cs.alloc_var(F::ZERO))
+        .collect::<Vec<Wire<F>>>();
+
+    if cs.is_witness_gen() {
+        let value = |terms: &[(Wire<F>, F)], neg: Wire<F>| -> BigInt {
+            let sum = terms.iter().fold(BigUint::from(0u32), |acc, (w, c)| {
+                let w: BigUint = cs.wires[w.index].into();
+                let c: BigUint = (*c).into();
+                acc + w * c
+            });
+
+            if cs.wires[neg.index] == F::ONE {
+                -BigInt::from(sum)
+            } else {
+                BigInt::from(sum)
+            }
+        };
+
+        let mut carry = BigInt::from(0u32);
+        let mut carry_values = Vec::with_capacity(carries.len());
+        for m in 0..carries.len() {
+            let k_m: BigUint = if m < k_limbs.len() {
+                cs.wires[k_limbs[m].index].into()
+            } else {
+                BigUint::from(0u32)
+            };
+
+            let d_m = value(&k1_terms[m][..], k1_neg) + value(&lambda_k2_terms[m][..], k2_neg)
+                - BigInt::from(k_m)
+                - value(&t_n_terms[m][..], t_neg)
+                + &carry;
+
+            carry = d_m >> LIMB_BITS;
+            carry_values.push(to_field_signed::<F>(&carry));
+        }
+
+        for (carry, carry_value) in carries.iter().zip(carry_values) {
+            cs.wires[carry.index] = carry_value;
+        }
+    }
+
+    let carry_offset = F::from(BigUint::from(1u32) << (CARRY_BITS - 1));
+    for carry in &carries {
+        let shifted = cs.add_const(*carry, carry_offset);
+        to_bits_le(shifted, CARRY_BITS);
+    }
+
+    // (1 - 2 * neg) * (terms)
+    let signed = |terms: &[(Wire<F>, F)], neg: Wire<F>, cs: &mut ConstraintSystem<F>| {
+        if terms.is_empty() {
+            zero
+        } else {
+            cs.deg_2_comb(&[(one, F::ONE), (neg, -F::from(2u32))], terms, &[])
+        }
+    };
+
+    let limb_base = F::from(BigUint::from(1u32) << LIMB_BITS);
+    let mut carry_prev = zero;
+    for m in 0..num_positions {
+        let k1_m = signed(&k1_terms[m][..], k1_neg, cs);
+        let lambda_k2_m = signed(&lambda_k2_terms[m][..], k2_neg, cs);
+        let t_n_m = signed(&t_n_terms[m][..], t_neg, cs);
+        let k_m = if m < k_limbs.len() { k_limbs[m] } else { zero };
+
+        let d_m = k1_m + lambda_k2_m - k_m - t_n_m + carry_prev;
+
+        if m == num_positions - 1 {
+            cs.assert_equal(d_m, zero, "k1 + lambda * k2 != k mod n");
+        } else {
+            let carry_shifted = cs.mul_const(carries[m], limb_base);
+            cs.assert_equal(d_m, carry_shifted, "k1 + lambda * k2 != k mod n");
+            carry_prev = carries[m];
+        }
+    }
+
+    // ########################################
+    // Joint double-and-add
+    // ########################################
+
+    // p1 = s1 * p, p2 = s2 * phi(p)
+    let p1_y = cs.deg_2_comb(
+        &[(one, F::ONE), (k1_neg, -F::from(2u32))],
+        &[(p.y, F::ONE)],
+        &[],
+    );
+    let p2_y = cs.deg_2_comb(
+        &[(one, F::ONE), (k2_neg, -F::from(2u32))],
+        &[(p.y, F::ONE)],
+        &[],
+    );
+    let p1 = AffinePoint::new(p.x, p1_y);
+    let p2 = AffinePoint::new(cs.mul_const(p.x, beta), p2_y);
+    let p1_p2 = ec_add_complete(p1, p2, cs);
+
+    let infinity = AffinePoint::new(zero, zero);
+    let table = [infinity, p1, p2, p1_p2];
+    let table_x = table.iter().map(|t| t.x).collect::<Vec<Wire<F>>>();
+    let table_y = table.iter().map(|t| t.y).collect::<Vec<Wire<F>>>();
+
+    let mut result = infinity;
+    for i in (0..GLV_BITS).rev() {
+        if i != GLV_BITS - 1 {
+            result = ec_double_complete(result, cs);
+        }
+
+        let sel = [k1_bits[i], k2_bits[i]];
+        let t = AffinePoint::new(select_wire(&table_x, &sel), select_wire(&table_y, &sel));
+        result = ec_add_complete(t, result, cs);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ec::{AffineRepr, CurveGroup};
+    use frontend::ark_ff::{BigInteger, PrimeField};
+    use frontend::ark_secp256k1::Affine as Secp256k1Affine;
+    use frontend::ark_secp256k1::Fr;
+
+    use super::*;
+    use crate::ec_mul;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    fn mul_glv_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let p_x = cs.alloc_priv_input();
+        let p_y = cs.alloc_priv_input();
+        let k_bits = cs.alloc_priv_inputs(256);
+
+        let p = AffinePoint::<F>::new(p_x, p_y);
+        p.assert_on_curve(cs);
+
+        let out = ec_mul_glv(p, &k_bits, cs);
+
+        cs.expose_public(out.x);
+        cs.expose_public(out.y);
+    }
+
+    fn mul_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let p_x = cs.alloc_priv_input();
+        let p_y = cs.alloc_priv_input();
+        let k_bits = cs.alloc_priv_inputs(256);
+
+        let out = ec_mul(AffinePoint::<F>::new(p_x, p_y), &k_bits, cs);
+
+        cs.expose_public(out.x);
+        cs.expose_public(out.y);
+    }
+
+    #[test]
+    fn test_glv_decompose() {
+        let n = secp256k1_order();
+        let lambda = from_hex(LAMBDA);
+
+        let mut scalars = [3u64, 123456789, u64::MAX]
+            .map(|k| BigUint::from(k) * BigUint::from(u64::MAX).pow(2))
+            .to_vec();
+
+        // Scalars around n, n / 2 and lambda, where the rounding of the decomposition
+        // and the signs of k1, k2 and the quotient flip, and the largest 256-bit scalar
+        let one = BigUint::from(1u32);
+        scalars.extend([
+            BigUint::from(0u32),
+            one.clone(),
+            &n - &one,
+            &n / 2u32,
+            &n / 2u32 + &one,
+            lambda.clone(),
+            &lambda - &one,
+            &n - &lambda,
+            (&one << 256) - &one,
+        ]);
+
+        let n = BigInt::from(n);
+        let lambda = BigInt::from(lambda);
+
+        for k in scalars {
+            let (k1, k2) = glv_decompose(&k);
+
+            assert!(k1.magnitude().bits() <= GLV_BITS as u64, "k1 of {}", k);
+            assert!(k2.magnitude().bits() <= GLV_BITS as u64, "k2 of {}", k);
+
+            let diff = k1 + &lambda * k2 - BigInt::from(k.clone());
+            assert_eq!(&diff % &n, BigInt::from(0u32));
+
+            let t = diff / &n;
+            assert!(t.magnitude().bits() <= QUOTIENT_BITS as u64, "t of {}", k);
+        }
+    }
+
+    #[test]
+    fn test_mul_glv() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| mul_glv_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        let p = (Secp256k1Affine::generator() * Fr::from(7u32)).into_affine();
+        let cases = [
+            Fr::from(3u32),
+            Fr::from(0u32),
+            -Fr::from(1u32),
+            Fr::from(123456789123456789u64) * Fr::from(987654321987654321u64),
+        ];
+
+        for k in cases {
+            let k_bits = k
+                .into_bigint()
+                .to_bits_le()
+                .iter()
+                .map(|b| Fp::from(*b))
+                .collect::<Vec<Fp>>();
+
+            let out = (p * k).into_affine();
+
+            let pub_input = vec![out.x, out.y];
+            let mut priv_input = vec![p.x, p.y];
+            priv_input.extend_from_slice(&k_bits);
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+
+        let mut naive_cs = ConstraintSystem::<Fp>::new();
+        naive_cs.set_constraints(&mul_circuit::<Fp>);
+
+        println!(
+            "Num constraints (ec_mul): {}",
+            naive_cs.num_constraints.unwrap()
+        );
+        println!(
+            "Num constraints (ec_mul_glv): {}",
+            cs.num_constraints.unwrap()
+        );
+
+        assert!(cs.num_constraints.unwrap() < naive_cs.num_constraints.unwrap());
+    }
+}
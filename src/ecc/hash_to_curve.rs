@@ -0,0 +1,410 @@
+use super::add::ec_add_complete;
+use super::compress::parity;
+use super::{from_hex, AffinePoint, CurveParams, Secp256k1};
+use crate::PoseidonSpongeChip;
+use ark_ec::{AffineRepr, CurveGroup};
+use frontend::ark_ff::{BigInteger, Field};
+use frontend::ark_secp256k1::{Affine as Secp256k1Affine, Fq};
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+use shockwave_plus::{IOPattern, PoseidonCurve, PoseidonSponge, SpongeOp};
+
+// Hashing to secp256k1 following the secp256k1_XMD:SHA-256_SSWU_RO_ suite of RFC 9380;
+// https://www.rfc-editor.org/rfc/rfc9380.html
+// The simplified SWU map requires a * b != 0, so it is applied to the 3-isogenous curve
+// E': y^2 = x^3 + A' * x + B', and the result is mapped back to secp256k1 with the isogeny.
+// The only deviation from the RFC is hash_to_field:
+// the message is hashed to field elements with Poseidon instead of expand_message_xmd.
+
+// A' and B' of the isogenous curve E'
+const ISO_A: &str = "3f8731abdd661adca08a5558f0f5d272e953d363cb6f0e5d405447c01a444533";
+const ISO_B: u32 = 1771;
+// The simplified SWU map uses the non-square Z = -11
+const SSWU_MINUS_Z: u32 = 11;
+
+// Coefficients of the 3-isogeny map from E' to secp256k1, lowest degree first.
+// (x, y) -> (x_num(x) / x_den(x), y * y_num(x) / y_den(x))
+const X_NUM: [&str; 4] = [
+    "8e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38daaaaa8c7",
+    "07d3d4c80bc321d5b9f315cea7fd44c5d595d2fc0bf63b92dfff1044f17c6581",
+    "534c328d23f234e6e2a413deca25caece4506144037c40314ecbd0b53d9dd262",
+    "8e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38e38daaaaa88c",
+];
+const X_DEN: [&str; 3] = [
+    "d35771193d94918a9ca34ccbb7b640dd86cd409542f8487d9fe6b745781eb49b",
+    "edadc6f64383dc1df7c4b2d51b54225406d36b641f5e41bbc52a56612a8c6d14",
+    "1",
+];
+const Y_NUM: [&str; 4] = [
+    "4bda12f684bda12f684bda12f684bda12f684bda12f684bda12f684b8e38e23c",
+    "c75e0c32d5cb7c0fa9d0a54b12a0a6d5647ab046d686da6fdffc90fc201d71a3",
+    "29a6194691f91a73715209ef6512e576722830a201be2018a765e85a9ecee931",
+    "2f684bda12f684bda12f684bda12f684bda12f684bda12f684bda12f38e38d84",
+];
+const Y_DEN: [&str; 4] = [
+    "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffff93b",
+    "7a06534bb8bdb49fd5e9e6632722c2989467c1bfc8e8d978dfb425d2685c2573",
+    "6484aa716545ca2cf3a70c3fa8fe337e0a3d21162f0d6299a7bf8192bfd2a76f",
+    "1",
+];
+
+const SPONGE_WIDTH: usize = 3;
+
+fn iso_a<F: FieldGC>() -> F {
+    F::from(from_hex(ISO_A))
+}
+
+fn iso_b<F: FieldGC>() -> F {
+    F::from(ISO_B)
+}
+
+fn sswu_z<F: FieldGC>() -> F {
+    -F::from(SSWU_MINUS_Z)
+}
+
+fn coeffs<F: FieldGC>(hex: &[&str]) -> Vec<F> {
+    hex.iter().map(|c| F::from(from_hex(c))).collect()
+}
+
+// The hash to field absorbs the whole message and squeezes two field elements
+fn io_pattern(msg_len: usize) -> IOPattern {
+    IOPattern::new(vec![SpongeOp::Absorb(msg_len), SpongeOp::Squeeze(2)])
+}
+
+// sgn0 of RFC 9380 for prime fields, i.e. the parity of the canonical representation
+fn sgn0<F: FieldGC>(x: F) -> bool {
+    x.into_bigint().is_odd()
+}
+
+// x^3 + A' * x + B'
+fn iso_curve_rhs_native<F: FieldGC>(x: F) -> F {
+    x * x * x + iso_a::<F>() * x + iso_b::<F>()
+}
+
+fn eval_poly_native<F: FieldGC>(coeffs: &[F], x: F) -> F {
+    coeffs.iter().rev().fold(F::ZERO, |acc, c| acc * x + c)
+}
+
+// The simplified SWU map to E' (Section 6.6.2 of RFC 9380)
+fn sswu_native<F: FieldGC>(u: F) -> (F, F) {
+    let a = iso_a::<F>();
+    let b = iso_b::<F>();
+    let z = sswu_z::<F>();
+
+    let u_sq = u * u;
+    let tv1 = z * z * u_sq * u_sq + z * u_sq;
+
+    let x1 = match tv1.inverse() {
+        Some(tv1_inv) => -b * a.inverse().unwrap() * (F::ONE + tv1_inv),
+        None => b * (z * a).inverse().unwrap(),
+    };
+    let x2 = z * u_sq * x1;
+
+    let (x, y) = match iso_curve_rhs_native(x1).sqrt() {
+        Some(y1) => (x1, y1),
+        None => (x2, iso_curve_rhs_native(x2).sqrt().unwrap()),
+    };
+
+    let y = if sgn0(u) == sgn0(y) { y } else { -y };
+    (x, y)
+}
+
+// The 3-isogeny from E' to secp256k1.
+// Points at which the denominators vanish are mapped to the identity (0, 0).
+fn iso_map_native<F: FieldGC>(x: F, y: F) -> (F, F) {
+    let x_num = eval_poly_native(&coeffs::<F>(&X_NUM), x);
+    let x_den = eval_poly_native(&coeffs::<F>(&X_DEN), x);
+    let y_num = eval_poly_native(&coeffs::<F>(&Y_NUM), x);
+    let y_den = eval_poly_native(&coeffs::<F>(&Y_DEN), x);
+
+    match (x_den.inverse(), y_den.inverse()) {
+        (Some(x_den_inv), Some(y_den_inv)) => (x_num * x_den_inv, y * y_num * y_den_inv),
+        _ => (F::ZERO, F::ZERO),
+    }
+}
+
+// Native counterpart of `map_to_curve`
+pub fn map_to_curve_native(u: Fq) -> Secp256k1Affine {
+    let (x, y) = sswu_native(u);
+    let (x, y) = iso_map_native(x, y);
+
+    if x == Fq::ZERO && y == Fq::ZERO {
+        Secp256k1Affine::identity()
+    } else {
+        Secp256k1Affine::new(x, y)
+    }
+}
+
+// Native counterpart of `hash_to_curve`
+pub fn hash_to_curve_native(msg: &[Fq], domain_separator: &[u8]) -> Secp256k1Affine {
+    let mut sponge = PoseidonSponge::<Fq, SPONGE_WIDTH>::new(
+        domain_separator,
+        PoseidonCurve::SECP256K1,
+        io_pattern(msg.len()),
+    );
+    sponge.absorb(msg);
+    let u = sponge.squeeze(2);
+
+    (map_to_curve_native(u[0]) + map_to_curve_native(u[1])).into_affine()
+}
+
+// x^3 + A' * x + B'
+fn iso_curve_rhs<F: FieldGC>(x: Wire<F>, cs: &mut ConstraintSystem<F>) -> Wire<F> {
+    let one = cs.one();
+    let x_sq = x * x;
+    cs.deg_2_comb(
+        &[(x_sq, F::ONE)],
+        &[(x, F::ONE)],
+        &[(x, iso_a::<F>()), (one, iso_b::<F>())],
+    )
+}
+
+// sum(coeffs[i] * powers[i]) as a single linear combination
+fn eval_poly<F: FieldGC>(
+    coeffs: &[F],
+    powers: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) -> Wire<F> {
+    let one = cs.one();
+    let terms = powers
+        .iter()
+        .zip(coeffs.iter())
+        .map(|(x, c)| (*x, *c))
+        .collect::<Vec<(Wire<F>, F)>>();
+
+    cs.deg_2_comb(&terms, &[(one, F::ONE)], &[])
+}
+
+// The simplified SWU map to E'.
+// Since gx2 = Z^3 * u^6 * gx1 and Z is a non-square, exactly one of gx1 and gx2 is a square,
+// so constraining y^2 to the selected one leaves the prover no choice.
+// (The exceptional inputs with Z^2 * u^4 + Z * u^2 = 0 are hit with negligible probability
+// when u is the output of a hash.)
+fn sswu<F: FieldGC>(u: Wire<F>, cs: &mut ConstraintSystem<F>) -> (Wire<F>, Wire<F>) {
+    let one = cs.one();
+    let a = iso_a::<F>();
+    let b = iso_b::<F>();
+    let z = sswu_z::<F>();
+
+    // tv1 = Z^2 * u^4 + Z * u^2
+    let u_sq = u * u;
+    let tv1 = cs.deg_2_comb(&[(u_sq, z * z)], &[(u_sq, F::ONE)], &[(u_sq, z)]);
+    let tv1_inv = one.div_or_zero(tv1);
+
+    // x1 = (-B / A) * (1 + 1 / tv1), or B / (Z * A) if tv1 = 0
+    let minus_b_over_a = -b * a.inverse().unwrap();
+    let x1 = cs.mul_const(tv1_inv, minus_b_over_a);
+    let x1 = cs.add_const(x1, minus_b_over_a);
+    let x1_exceptional = cs.alloc_const(b * (z * a).inverse().unwrap());
+    let x1 = cs.if_then(tv1.is_zero(), x1_exceptional).else_then(x1);
+    let gx1 = iso_curve_rhs(x1, cs);
+
+    // x2 = Z * u^2 * x1
+    let x2 = cs.deg_2_comb(&[(u_sq, z)], &[(x1, F::ONE)], &[]);
+    let gx2 = iso_curve_rhs(x2, cs);
+
+    let is_gx1_square = cs.alloc_var(F::ZERO);
+    let y = cs.alloc_var(F::ZERO);
+
+    if cs.is_witness_gen() {
+        let gx1_assigned = cs.wires[gx1.index];
+        let (_, y_assigned) = sswu_native(cs.wires[u.index]);
+
+        cs.wires[is_gx1_square.index] = F::from(gx1_assigned.sqrt().is_some());
+        cs.wires[y.index] = y_assigned;
+    }
+
+    cs.assert_equal(
+        is_gx1_square * is_gx1_square,
+        is_gx1_square,
+        "is_gx1_square is not a bit",
+    );
+
+    let x = cs.if_then(is_gx1_square, x1).else_then(x2);
+    let gx = cs.if_then(is_gx1_square, gx1).else_then(gx2);
+    cs.assert_equal(y * y, gx, "y is not a square root of g(x)");
+
+    // sgn0(y) = sgn0(u)
+    let u_is_odd = parity(u, cs);
+    let y_is_odd = parity(y, cs);
+    cs.assert_equal(u_is_odd, y_is_odd, "sign of y mismatch");
+
+    (x, y)
+}
+
+// The 3-isogeny from E' to secp256k1.
+// Points at which the denominators vanish are mapped to the identity (0, 0).
+fn iso_map<F: FieldGC>(x: Wire<F>, y: Wire<F>, cs: &mut ConstraintSystem<F>) -> AffinePoint<F>
+where
+    Secp256k1: CurveParams<F>,
+{
+    let one = cs.one();
+    let x_sq = x * x;
+    let x_cu = x_sq * x;
+    let powers = [one, x, x_sq, x_cu];
+
+    let x_num = eval_poly(&coeffs::<F>(&X_NUM), &powers, cs);
+    let x_den = eval_poly(&coeffs::<F>(&X_DEN), &powers, cs);
+    let y_num = eval_poly(&coeffs::<F>(&Y_NUM), &powers, cs);
+    let y_den = eval_poly(&coeffs::<F>(&Y_DEN), &powers, cs);
+
+    let out_x = x_num.div_or_zero(x_den);
+    let out_y = y * y_num.div_or_zero(y_den);
+
+    AffinePoint::new(out_x, out_y)
+}
+
+// Maps the field element u to a point on secp256k1 (map_to_curve of RFC 9380).
+// The output is constrained to be the unique point determined by u.
+pub fn map_to_curve<F: FieldGC>(u: Wire<F>, cs: &mut ConstraintSystem<F>) -> AffinePoint<F>
+where
+    Secp256k1: CurveParams<F>,
+{
+    let (x, y) = sswu(u, cs);
+    iso_map(x, y, cs)
+}
+
+// Hashes the message to a point on secp256k1 (hash_to_curve of RFC 9380),
+// where the hash to field is a Poseidon sponge with the given domain separator.
+// secp256k1 has cofactor 1, so no cofactor clearing is needed.
+pub fn hash_to_curve<F: FieldGC>(
+    msg: &[Wire<F>],
+    domain_separator: &[u8],
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F>
+where
+    Secp256k1: CurveParams<F>,
+{
+    let mut sponge = PoseidonSpongeChip::<F, SPONGE_WIDTH>::new(
+        domain_separator,
+        io_pattern(msg.len()),
+        PoseidonCurve::SECP256K1,
+        cs,
+    );
+    sponge.absorb(msg);
+    let u = sponge.squeeze(2);
+
+    let q0 = map_to_curve(u[0], cs);
+    let q1 = map_to_curve(u[1], cs);
+
+    // q0 = q1 only with negligible probability, so the addition never needs to double
+    ec_add_complete(q0, q1, cs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    const DOMAIN_SEPARATOR: &[u8] = b"test-hash-to-curve";
+    const MSG_LEN: usize = 2;
+
+    // Test vectors of the secp256k1_XMD:SHA-256_SSWU_RO_ suite (RFC 9380, Appendix J.8.1):
+    // (u0, u1, Q0.x, Q0.y, Q1.x, Q1.y, P.x, P.y)
+    const TEST_VECTORS: [[&str; 8]; 2] = [
+        // msg = ""
+        [
+            "6b0f9910dd2ba71c78f2ee9f04d73b5f4c5f7fc773a701abea1e573cab002fb3",
+            "1ae6c212e08fe1a5937f6202f929a2cc8ef4ee5b9782db68b0d5799fd8f09e16",
+            "74519ef88b32b425a095e4ebcc84d81b64e9e2c2675340a720bb1a1857b99f1e",
+            "c174fa322ab7c192e11748beed45b508e9fdb1ce046dee9c2cd3a2a86b410936",
+            "44548adb1b399263ded3510554d28b4bead34b8cf9a37b4bd0bd2ba4db87ae63",
+            "96eb8e2faf05e368efe5957c6167001760233e6dd2487516b46ae725c4cce0c6",
+            "c1cae290e291aee617ebaef1be6d73861479c48b841eaba9b7b5852ddfeb1346",
+            "64fa678e07ae116126f08b022a94af6de15985c996c3a91b64c406a960e51067",
+        ],
+        // msg = "abc"
+        [
+            "128aab5d3679a1f7601e3bdf94ced1f43e491f544767e18a4873f397b08a2b61",
+            "5897b65da3b595a813d0fdcc75c895dc531be76a03518b044daaa0f2e4689e00",
+            "07dd9432d426845fb19857d1b3a91722436604ccbbbadad8523b8fc38a5322d7",
+            "604588ef5138cffe3277bbd590b8550bcbe0e523bbaf1bed4014a467122eb33f",
+            "e9ef9794d15d4e77dde751e06c182782046b8dac05f8491eb88764fc65321f78",
+            "cb07ce53670d5314bf236ee2c871455c562dd76314aa41f012919fe8e7f717b3",
+            "3377e01eab42db296b512293120c6cee72b6ecf9f9205760bd9ff11fb3cb2c4b",
+            "7f95890f33efebd1044d382a01b1bee0900fb6116f94688d487c6c7b9c8371f6",
+        ],
+    ];
+
+    fn to_fp(hex: &str) -> Fp {
+        Fp::from(from_hex(hex))
+    }
+
+    #[test]
+    fn test_map_to_curve_native() {
+        for v in TEST_VECTORS {
+            let q0 = map_to_curve_native(to_fp(v[0]));
+            let q1 = map_to_curve_native(to_fp(v[1]));
+
+            assert_eq!(q0, Secp256k1Affine::new(to_fp(v[2]), to_fp(v[3])));
+            assert_eq!(q1, Secp256k1Affine::new(to_fp(v[4]), to_fp(v[5])));
+            assert_eq!(
+                (q0 + q1).into_affine(),
+                Secp256k1Affine::new(to_fp(v[6]), to_fp(v[7]))
+            );
+        }
+    }
+
+    fn map_to_curve_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let u = cs.alloc_priv_input();
+
+        let p = map_to_curve(u, cs);
+        p.assert_on_curve(cs);
+
+        cs.expose_public(p.x);
+        cs.expose_public(p.y);
+    }
+
+    #[test]
+    fn test_map_to_curve() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| map_to_curve_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+
+        for v in TEST_VECTORS {
+            for (u, x, y) in [(v[0], v[2], v[3]), (v[1], v[4], v[5])] {
+                let priv_input = [to_fp(u)];
+                let pub_input = [to_fp(x), to_fp(y)];
+
+                let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+                assert!(cs.is_sat(&witness, &pub_input));
+            }
+        }
+    }
+
+    fn hash_to_curve_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let msg = cs.alloc_priv_inputs(MSG_LEN);
+
+        let p = hash_to_curve(&msg, DOMAIN_SEPARATOR, cs);
+
+        cs.expose_public(p.x);
+        cs.expose_public(p.y);
+    }
+
+    #[test]
+    fn test_hash_to_curve() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| hash_to_curve_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+
+        let msg = [Fp::from(42u32), Fp::from(1234567u32)];
+        let expected = hash_to_curve_native(&msg, DOMAIN_SEPARATOR);
+
+        let pub_input = [expected.x, expected.y];
+        let witness = cs.gen_witness(synthesizer, &pub_input, &msg);
+        assert!(cs.is_sat(&witness, &pub_input));
+    }
+}
@@ -1,18 +1,363 @@
+use frontend::ark_ff::PrimeField;
 use frontend::FieldGC;
-use frontend::Wire;
+use frontend::{ConstraintSystem, Wire};
+use num_bigint::BigUint;
+use std::marker::PhantomData;
 
 pub mod add;
+pub mod compress;
+pub mod curves;
 pub mod double;
+pub mod ecdsa;
+pub mod efficient_ecdsa;
+pub mod glv;
+pub mod hash_to_curve;
+pub mod msm;
 pub mod mul;
+pub mod pedersen;
+pub mod plume;
+pub mod projective;
+pub mod schnorr;
 
+pub use curves::{CurveParams, Secp256k1, Secq256k1};
+
+// A point on the curve C in affine coordinates. Defaults to secp256k1.
 #[derive(Copy, Clone)]
-pub struct AffinePoint<F: FieldGC> {
+pub struct AffinePoint<F: FieldGC, C: CurveParams<F> = Secp256k1> {
     pub x: Wire<F>,
     pub y: Wire<F>,
+    _curve: PhantomData<C>,
 }
 
-impl<F: FieldGC> AffinePoint<F> {
+impl<F: FieldGC, C: CurveParams<F>> AffinePoint<F, C> {
     pub fn new(x: Wire<F>, y: Wire<F>) -> Self {
-        Self { x, y }
+        Self {
+            x,
+            y,
+            _curve: PhantomData,
+        }
+    }
+
+    // The generator of the curve as a constant point
+    pub fn generator(cs: &mut ConstraintSystem<F>) -> Self {
+        let (g_x, g_y) = C::generator();
+        Self::new(cs.alloc_const(g_x), cs.alloc_const(g_y))
+    }
+
+    // y^2 - (x^3 + a * x + b), which is zero iff the point is on the curve
+    fn curve_equation(&self, cs: &mut ConstraintSystem<F>) -> Wire<F> {
+        let y_sq = self.y * self.y;
+        let mut rhs = self.x * self.x * self.x;
+        if C::a() != F::ZERO {
+            rhs += cs.mul_const(self.x, C::a());
+        }
+        let rhs = cs.add_const(rhs, C::b());
+
+        y_sq - rhs
+    }
+
+    // Constrains the point to satisfy the curve equation y^2 = x^3 + a * x + b.
+    // The identity (0, 0) doesn't satisfy the equation and is rejected.
+    pub fn assert_on_curve(&self, cs: &mut ConstraintSystem<F>) {
+        let zero = cs.alloc_const(F::ZERO);
+        let lhs = self.curve_equation(cs);
+        cs.assert_equal(lhs, zero, "point is not on the curve");
+    }
+
+    // Constrains the point to be either on the curve or the identity (0, 0).
+    // See `CurveParams::assert_no_zero_coordinates` for why (0, 0) is free to encode the identity.
+    pub fn assert_on_curve_or_identity(&self, cs: &mut ConstraintSystem<F>) {
+        C::assert_no_zero_coordinates();
+
+        let zero = cs.alloc_const(F::ZERO);
+        let one = cs.one();
+
+        let is_identity = self.x.is_zero() & self.y.is_zero();
+        let lhs = self.curve_equation(cs) * (one - is_identity);
+        cs.assert_equal(lhs, zero, "point is not on the curve nor the identity");
+    }
+}
+
+// Converts a field element into another prime field through its integer representation.
+// Used to run native curve arithmetic (e.g. over `ark_secp256k1`) during witness generation.
+pub(crate) fn to_field<F: PrimeField, G: PrimeField>(x: F) -> G {
+    let x: BigUint = x.into();
+    G::from(x)
+}
+
+// Parses a big-endian hex string (without the 0x prefix)
+pub(crate) fn from_hex(hex: &str) -> BigUint {
+    BigUint::parse_bytes(hex.as_bytes(), 16).unwrap()
+}
+
+// The order of the secp256k1 group
+pub(crate) fn secp256k1_order() -> BigUint {
+    frontend::ark_secp256k1::Fr::MODULUS.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ec::{AffineRepr, CurveGroup};
+    use frontend::ark_ff::Field;
+    use frontend::ark_secp256k1::Affine as Secp256k1Affine;
+    use frontend::ark_secp256k1::Fr;
+
+    use super::*;
+    use crate::test_utils::is_rejected;
+    use crate::{ec_add_complete, ec_double_complete, ec_mul};
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    // y^2 = x^3 - 3 * x + 5 over the secp256k1 base field
+    #[derive(Copy, Clone)]
+    struct TestCurve;
+
+    impl CurveParams<Fp> for TestCurve {
+        fn a() -> Fp {
+            -Fp::from(3u32)
+        }
+
+        fn b() -> Fp {
+            Fp::from(5u32)
+        }
+
+        // The point with the smallest x coordinate
+        fn generator() -> (Fp, Fp) {
+            smallest_point::<Self>()
+        }
+    }
+
+    // y^2 = x^3 - 3 * x + 7, where x^3 - 3 * x + 7 has a root
+    #[derive(Copy, Clone)]
+    struct CurveWithRoot;
+
+    impl CurveParams<Fp> for CurveWithRoot {
+        fn a() -> Fp {
+            -Fp::from(3u32)
+        }
+
+        fn b() -> Fp {
+            Fp::from(7u32)
+        }
+
+        fn generator() -> (Fp, Fp) {
+            smallest_point::<Self>()
+        }
+    }
+
+    // y^2 = x^3 + 4, which has the points (0, 2) and (0, -2)
+    #[derive(Copy, Clone)]
+    struct CurveWithResidueB;
+
+    impl CurveParams<Fp> for CurveWithResidueB {
+        fn a() -> Fp {
+            Fp::from(0u32)
+        }
+
+        fn b() -> Fp {
+            Fp::from(4u32)
+        }
+
+        fn generator() -> (Fp, Fp) {
+            (Fp::from(0u32), Fp::from(2u32))
+        }
+    }
+
+    fn smallest_point<C: CurveParams<Fp>>() -> (Fp, Fp) {
+        let mut x = Fp::ONE;
+        loop {
+            let rhs = x * x * x + C::a() * x + C::b();
+            if let Some(y) = rhs.sqrt() {
+                return (x, y);
+            }
+            x += Fp::ONE;
+        }
+    }
+
+    // Native affine arithmetic over the curve C, with the identity encoded as None
+    fn native_add<F: FieldGC, C: CurveParams<F>>(
+        p: Option<(F, F)>,
+        q: Option<(F, F)>,
+    ) -> Option<(F, F)> {
+        let ((p_x, p_y), (q_x, q_y)) = match (p, q) {
+            (None, _) => return q,
+            (_, None) => return p,
+            (Some(p), Some(q)) => (p, q),
+        };
+
+        if p_x == q_x && p_y == -q_y {
+            return None;
+        }
+
+        let lambda = if p_x == q_x {
+            (F::from(3u32) * p_x * p_x + C::a()) / (F::from(2u32) * p_y)
+        } else {
+            (q_y - p_y) / (q_x - p_x)
+        };
+
+        let x = lambda * lambda - p_x - q_x;
+        let y = lambda * (p_x - x) - p_y;
+        Some((x, y))
+    }
+
+    fn native_mul<F: FieldGC, C: CurveParams<F>>(p: (F, F), s: u64) -> Option<(F, F)> {
+        let mut result = None;
+        let mut current = Some(p);
+        for i in 0..64 {
+            if (s >> i) & 1 == 1 {
+                result = native_add::<F, C>(result, current);
+            }
+            current = native_add::<F, C>(current, current);
+        }
+
+        result
+    }
+
+    fn test_curve_circuit<F: FieldGC, C: CurveParams<F>>(cs: &mut ConstraintSystem<F>) {
+        let p_x = cs.alloc_priv_input();
+        let p_y = cs.alloc_priv_input();
+        let s_bits = cs.alloc_priv_inputs(64);
+
+        let p = AffinePoint::<F, C>::new(p_x, p_y);
+        p.assert_on_curve(cs);
+
+        let g = AffinePoint::<F, C>::generator(cs);
+
+        let doubled = ec_double_complete(p, cs);
+        let sum = ec_add_complete(doubled, g, cs);
+        let out = ec_mul(sum, &s_bits, cs);
+
+        cs.expose_public(out.x);
+        cs.expose_public(out.y);
+    }
+
+    // Checks the gadgets against the native arithmetic on the curve C
+    fn check_curve<F: FieldGC, C: CurveParams<F>>() {
+        let synthesizer = |cs: &mut ConstraintSystem<F>| test_curve_circuit::<F, C>(cs);
+
+        // The generator is on the curve
+        let g = C::generator();
+        assert_eq!(g.1 * g.1, g.0 * g.0 * g.0 + C::a() * g.0 + C::b());
+
+        let p = native_mul::<F, C>(g, 5).unwrap();
+        let s = 123456789u64;
+
+        // (2 * p + g) * s
+        let doubled = native_add::<F, C>(Some(p), Some(p));
+        let sum = native_add::<F, C>(doubled, Some(g)).unwrap();
+        let out = native_mul::<F, C>(sum, s).unwrap();
+
+        let mut priv_input = vec![p.0, p.1];
+        priv_input.extend((0..64).map(|i| F::from((s >> i) & 1)));
+        let pub_input = [out.0, out.1];
+
+        let mut cs = ConstraintSystem::new();
+        let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+
+        cs.set_constraints(&synthesizer);
+        assert!(cs.is_sat(&witness, &pub_input));
+    }
+
+    #[test]
+    fn test_curve_with_nonzero_a() {
+        check_curve::<Fp, TestCurve>();
+    }
+
+    #[test]
+    fn test_secq256k1() {
+        // secq256k1 is defined over the secp256k1 scalar field
+        check_curve::<Fr, Secq256k1>();
+
+        // The native arithmetic agrees with ark-secp256k1 on secp256k1
+        let g = Secp256k1Affine::generator();
+        let p = native_mul::<Fp, Secp256k1>((g.x, g.y), 123456789).unwrap();
+        let expected = (g * Fr::from(123456789u64)).into_affine();
+        assert_eq!(p, (expected.x, expected.y));
+    }
+
+    #[test]
+    fn test_no_zero_coordinates() {
+        <Secp256k1 as CurveParams<Fp>>::assert_no_zero_coordinates();
+        <Secq256k1 as CurveParams<Fr>>::assert_no_zero_coordinates();
+        TestCurve::assert_no_zero_coordinates();
+    }
+
+    #[test]
+    #[should_panic(expected = "x^3 + a * x + b has a root")]
+    fn test_curve_with_root() {
+        CurveWithRoot::assert_no_zero_coordinates();
+    }
+
+    #[test]
+    #[should_panic(expected = "b is a residue")]
+    fn test_curve_with_residue_b() {
+        CurveWithResidueB::assert_no_zero_coordinates();
+    }
+
+    #[test]
+    fn test_assert_on_curve() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let p_x = cs.alloc_priv_input();
+            let p_y = cs.alloc_priv_input();
+            let p = AffinePoint::<Fp>::new(p_x, p_y);
+
+            p.assert_on_curve(cs);
+
+            cs.expose_public(p.x);
+        };
+
+        let p = (Secp256k1Affine::generator() * Fr::from(3u32)).into_affine();
+
+        let pub_input = [p.x];
+        let priv_input = [p.x, p.y];
+
+        let mut cs = ConstraintSystem::new();
+        let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+
+        cs.set_constraints(&synthesizer);
+        assert!(cs.is_sat(&witness, &pub_input));
+
+        // Off-curve points, including the identity, are rejected
+        let zero = Fp::from(0u32);
+        let one = Fp::from(1u32);
+        for (x, y) in [(p.x, p.y + one), (p.x + one, p.y), (zero, zero)] {
+            let assertions = ["point is not on the curve"];
+            assert!(is_rejected(synthesizer, &[x], &[x, y], &assertions));
+        }
+    }
+
+    #[test]
+    fn test_assert_on_curve_or_identity() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let p_x = cs.alloc_priv_input();
+            let p_y = cs.alloc_priv_input();
+            let p = AffinePoint::<Fp>::new(p_x, p_y);
+
+            p.assert_on_curve_or_identity(cs);
+
+            cs.expose_public(p.x);
+        };
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        let zero = Secp256k1Affine::identity();
+        let p_nonzero = (Secp256k1Affine::generator() * Fr::from(3u32)).into_affine();
+
+        for p in [zero, p_nonzero] {
+            let pub_input = [p.x];
+            let priv_input = [p.x, p.y];
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+
+        // Off-curve points other than the identity are rejected
+        let zero = Fp::from(0u32);
+        let one = Fp::from(1u32);
+        for (x, y) in [(p_nonzero.x, p_nonzero.y + one), (zero, one), (one, zero)] {
+            let assertions = ["point is not on the curve nor the identity"];
+            assert!(is_rejected(synthesizer, &[x], &[x, y], &assertions));
+        }
     }
 }
@@ -0,0 +1,140 @@
+use super::double::ec_double_complete;
+use super::{add::ec_add_complete, AffinePoint, CurveParams};
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+
+// Multi-scalar multiplication with Straus's (a.k.a. Shamir's) trick.
+// The scalars are processed MSB first and all terms share a single doubling chain,
+// so computing sum(s_i * p_i) costs one doubling per bit plus one addition per bit per term.
+// A term may equal the running sum (e.g. when points repeat), which the complete addition handles.
+// `scalars_bits` are interpreted as LSB first, and may be of different lengths.
+pub fn ec_msm<F: FieldGC, C: CurveParams<F>>(
+    points: &[AffinePoint<F, C>],
+    scalars_bits: &[Vec<Wire<F>>],
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F, C> {
+    assert_eq!(points.len(), scalars_bits.len());
+
+    let zero = cs.alloc_const(F::ZERO);
+    let infinity = AffinePoint::new(zero, zero);
+
+    let num_bits = scalars_bits
+        .iter()
+        .map(|bits| bits.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut result = infinity;
+    for i in (0..num_bits).rev() {
+        if i != num_bits - 1 {
+            result = ec_double_complete(result, cs);
+        }
+
+        for (p, s_bits) in points.iter().zip(scalars_bits.iter()) {
+            if i >= s_bits.len() {
+                continue;
+            }
+
+            let s_i = s_bits[i];
+            let t = AffinePoint::new(s_i * p.x, s_i * p.y);
+            result = ec_add_complete(t, result, cs);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ec::{AffineRepr, CurveGroup, VariableBaseMSM};
+    use frontend::ark_ff::{BigInteger, PrimeField};
+    use frontend::ark_secp256k1::Fr;
+    use frontend::ark_secp256k1::{Affine as Secp256k1Affine, Projective as Secp256k1Projective};
+
+    use super::*;
+    use crate::Secp256k1;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    const NUM_TERMS: usize = 3;
+
+    fn msm_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let mut points = Vec::with_capacity(NUM_TERMS);
+        let mut scalars_bits = Vec::with_capacity(NUM_TERMS);
+        for _ in 0..NUM_TERMS {
+            let p_x = cs.alloc_priv_input();
+            let p_y = cs.alloc_priv_input();
+            let p = AffinePoint::<F>::new(p_x, p_y);
+            p.assert_on_curve(cs);
+
+            points.push(p);
+            scalars_bits.push(cs.alloc_priv_inputs(256));
+        }
+
+        let out = ec_msm(&points, &scalars_bits, cs);
+
+        cs.expose_public(out.x);
+        cs.expose_public(out.y);
+    }
+
+    #[test]
+    pub fn test_msm() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| msm_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+
+        let g = Secp256k1Affine::generator();
+        let points = [
+            (g * Fr::from(5u32)).into_affine(),
+            (g * Fr::from(1234567u32)).into_affine(),
+            (g * -Fr::from(42u32)).into_affine(),
+        ];
+        let g2 = (g * Fr::from(2u32)).into_affine();
+
+        let cases = [
+            (points, [Fr::from(3u32), Fr::from(7u32), Fr::from(11u32)]),
+            (
+                points,
+                [Fr::from(0u32), -Fr::from(1u32), Fr::from(123456789u64)],
+            ),
+            // Duplicate points
+            (
+                [points[0], points[0], points[1]],
+                [Fr::from(1u32), Fr::from(1u32), Fr::from(0u32)],
+            ),
+            (
+                [g, g, points[2]],
+                [Fr::from(3u32), Fr::from(5u32), Fr::from(7u32)],
+            ),
+            // The second term equals the running sum 2 * g
+            (
+                [g, g2, points[0]],
+                [Fr::from(2u32), Fr::from(1u32), Fr::from(0u32)],
+            ),
+        ];
+
+        for (points, scalars) in cases {
+            let out = Secp256k1Projective::msm(&points, &scalars)
+                .unwrap()
+                .into_affine();
+
+            let mut priv_input = vec![];
+            for (p, s) in points.iter().zip(scalars.iter()) {
+                priv_input.push(p.x);
+                priv_input.push(p.y);
+                priv_input.extend(s.into_bigint().to_bits_le().iter().map(|b| Fp::from(*b)));
+            }
+
+            let pub_input = vec![out.x, out.y];
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
+}
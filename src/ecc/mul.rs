@@ -1,15 +1,21 @@
-use crate::ec_double;
+use crate::ec_double_complete;
 
-use super::{add::ec_add_complete, AffinePoint};
+use super::{add::ec_add_complete, to_field, AffinePoint, CurveParams, Secp256k1};
+use ark_ec::{CurveGroup, Group};
+use ark_std::Zero;
+use frontend::ark_secp256k1::{Affine as Secp256k1Affine, Projective as Secp256k1Projective};
 use frontend::FieldGC;
 use frontend::{ConstraintSystem, Wire};
 
+// Number of scalar bits processed per table lookup in the windowed multiplications
+const WINDOW_SIZE: usize = 4;
+
 // Naive double-and-add algorithm
-pub fn ec_mul<F: FieldGC>(
-    p: AffinePoint<F>,
+pub fn ec_mul<F: FieldGC, C: CurveParams<F>>(
+    p: AffinePoint<F, C>,
     s_bits: &[Wire<F>],
     cs: &mut ConstraintSystem<F>,
-) -> AffinePoint<F> {
+) -> AffinePoint<F, C> {
     let infinity = AffinePoint::new(cs.alloc_const(F::ZERO), cs.alloc_const(F::ZERO));
     let mut result = infinity;
     let mut current = p;
@@ -20,7 +26,7 @@ pub fn ec_mul<F: FieldGC>(
         let t = AffinePoint::new(t_x, t_y);
 
         result = ec_add_complete(t, result, cs);
-        current = ec_double(current, cs);
+        current = ec_double_complete(current, cs);
     }
 
     result.x.println();
@@ -28,6 +34,155 @@ pub fn ec_mul<F: FieldGC>(
     result
 }
 
+// Selects `table[index]` where `index` is given by `bits` (LSB first).
+// The table has 2^bits.len() entries.
+fn select_const<F: FieldGC>(table: &[F], bits: &[Wire<F>]) -> Wire<F> {
+    assert_eq!(table.len(), 1 << bits.len());
+    let cs = bits[0].cs();
+
+    // The first level selects between constants, which is linear in the bit
+    let mut level = table
+        .chunks(2)
+        .map(|pair| {
+            let diff = cs.mul_const(bits[0], pair[1] - pair[0]);
+            cs.add_const(diff, pair[0])
+        })
+        .collect::<Vec<Wire<F>>>();
+
+    for bit in &bits[1..] {
+        level = level
+            .chunks(2)
+            .map(|pair| select(*bit, pair[1], pair[0]))
+            .collect();
+    }
+
+    level[0]
+}
+
+// Selects `table[index]` where `index` is given by `bits` (LSB first).
+pub(crate) fn select_wire<F: FieldGC>(table: &[Wire<F>], bits: &[Wire<F>]) -> Wire<F> {
+    assert_eq!(table.len(), 1 << bits.len());
+
+    let mut level = table.to_vec();
+    for bit in bits {
+        level = level
+            .chunks(2)
+            .map(|pair| select(*bit, pair[1], pair[0]))
+            .collect();
+    }
+
+    level[0]
+}
+
+// Returns `a` if `bit` is 1, and `b` otherwise
+fn select<F: FieldGC>(bit: Wire<F>, a: Wire<F>, b: Wire<F>) -> Wire<F> {
+    let cs = bit.cs();
+    // b + bit * (a - b)
+    cs.deg_2_comb(
+        &[(bit, F::ONE)],
+        &[(a, F::ONE), (b, -F::ONE)],
+        &[(b, F::ONE)],
+    )
+}
+
+// Pads the scalar bits with zeros so that they split evenly into windows
+fn pad_to_windows<F: FieldGC>(s_bits: &[Wire<F>], cs: &mut ConstraintSystem<F>) -> Vec<Wire<F>> {
+    let zero = cs.alloc_const(F::ZERO);
+    let mut bits = s_bits.to_vec();
+    while bits.len() % WINDOW_SIZE != 0 {
+        bits.push(zero);
+    }
+
+    bits
+}
+
+// Windowed double-and-add.
+// We compute the table j * p for all j < 2^WINDOW_SIZE in the circuit,
+// and then process the scalar MSB first; each window costs WINDOW_SIZE doublings
+// and a single addition of the selected multiple, instead of one addition per bit.
+// The scalar is assumed to be less than the group order.
+pub fn ec_mul_windowed<F: FieldGC, C: CurveParams<F>>(
+    p: AffinePoint<F, C>,
+    s_bits: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F, C> {
+    let bits = pad_to_windows(s_bits, cs);
+
+    let infinity = AffinePoint::new(cs.alloc_const(F::ZERO), cs.alloc_const(F::ZERO));
+
+    // table[j] = j * p
+    let mut table = Vec::with_capacity(1 << WINDOW_SIZE);
+    table.push(infinity);
+    table.push(p);
+    table.push(ec_double_complete(p, cs));
+    for j in 3..(1 << WINDOW_SIZE) {
+        let next = ec_add_complete(table[j - 1], p, cs);
+        table.push(next);
+    }
+
+    let table_x = table.iter().map(|t| t.x).collect::<Vec<Wire<F>>>();
+    let table_y = table.iter().map(|t| t.y).collect::<Vec<Wire<F>>>();
+
+    let mut result = infinity;
+    for (i, window) in bits.chunks(WINDOW_SIZE).rev().enumerate() {
+        if i != 0 {
+            for _ in 0..WINDOW_SIZE {
+                result = ec_double_complete(result, cs);
+            }
+        }
+
+        let t = AffinePoint::new(select_wire(&table_x, window), select_wire(&table_y, window));
+        result = ec_add_complete(t, result, cs);
+    }
+
+    result
+}
+
+// Scalar multiplication by a base known at synthesis time (e.g. the generator).
+// For the i-th window of the scalar, we precompute j * 2^(WINDOW_SIZE * i) * base
+// for all j, so the circuit only selects a constant from each table and adds it up.
+// No doublings are needed.
+pub fn ec_mul_fixed_base<F: FieldGC>(
+    base: Secp256k1Affine,
+    s_bits: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F>
+where
+    Secp256k1: CurveParams<F>,
+{
+    let bits = pad_to_windows(s_bits, cs);
+
+    let infinity = AffinePoint::new(cs.alloc_const(F::ZERO), cs.alloc_const(F::ZERO));
+    let mut result = infinity;
+
+    let mut window_base = Secp256k1Projective::from(base);
+    for window in bits.chunks(WINDOW_SIZE) {
+        // Precompute the multiples of the window base.
+        // The identity is encoded as (0, 0).
+        let mut table_x = Vec::with_capacity(1 << WINDOW_SIZE);
+        let mut table_y = Vec::with_capacity(1 << WINDOW_SIZE);
+        let mut multiple = Secp256k1Projective::zero();
+        for _ in 0..(1 << WINDOW_SIZE) {
+            let multiple_affine = multiple.into_affine();
+            table_x.push(to_field(multiple_affine.x));
+            table_y.push(to_field(multiple_affine.y));
+            multiple += window_base;
+        }
+
+        let t = AffinePoint::new(
+            select_const(&table_x, window),
+            select_const(&table_y, window),
+        );
+        result = ec_add_complete(t, result, cs);
+
+        for _ in 0..WINDOW_SIZE {
+            window_base.double_in_place();
+        }
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use frontend::ark_ff::BigInteger;
@@ -49,6 +204,7 @@ mod tests {
             let s_bits = cs.alloc_priv_inputs(256);
 
             let p = AffinePoint::<Fp>::new(p_x, p_y);
+            p.assert_on_curve(cs);
 
             let out = ec_mul(p, &s_bits, cs);
 
@@ -82,4 +238,142 @@ mod tests {
 
         assert!(cs.is_sat(&witness, &pub_input));
     }
+
+    #[test]
+    pub fn test_mul_fixed_base() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let s_bits = cs.alloc_priv_inputs(256);
+
+            let out = ec_mul_fixed_base(Secp256k1Affine::generator(), &s_bits, cs);
+
+            cs.expose_public(out.x);
+            cs.expose_public(out.y);
+        };
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        let num_constraints = cs.num_constraints.unwrap();
+        println!("Num constraints: {}", num_constraints);
+
+        let cases = [Fr::from(3u32), Fr::from(0u32), -Fr::from(1u32)];
+
+        for s in cases {
+            let s_bits = s
+                .into_bigint()
+                .to_bits_le()
+                .iter()
+                .map(|b| Fp::from(*b))
+                .collect::<Vec<Fp>>();
+
+            let out = (Secp256k1Affine::generator() * s).into_affine();
+
+            let pub_input = vec![out.x, out.y];
+            let priv_input = s_bits;
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+
+        // Compare against the variable-base multiplication
+        let var_base_synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let p_x = cs.alloc_priv_input();
+            let p_y = cs.alloc_priv_input();
+            let s_bits = cs.alloc_priv_inputs(256);
+
+            let out = ec_mul(AffinePoint::<Fp>::new(p_x, p_y), &s_bits, cs);
+
+            cs.expose_public(out.x);
+            cs.expose_public(out.y);
+        };
+
+        let mut var_base_cs = ConstraintSystem::new();
+        var_base_cs.set_constraints(&var_base_synthesizer);
+
+        let var_base_num_constraints = var_base_cs.num_constraints.unwrap();
+        println!("Num constraints (ec_mul): {}", var_base_num_constraints);
+
+        assert!(num_constraints < var_base_num_constraints);
+    }
+
+    fn mul_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let p_x = cs.alloc_priv_input();
+        let p_y = cs.alloc_priv_input();
+        let s_bits = cs.alloc_priv_inputs(256);
+
+        let out = ec_mul(AffinePoint::<F>::new(p_x, p_y), &s_bits, cs);
+
+        cs.expose_public(out.x);
+        cs.expose_public(out.y);
+    }
+
+    fn mul_windowed_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let p_x = cs.alloc_priv_input();
+        let p_y = cs.alloc_priv_input();
+        let s_bits = cs.alloc_priv_inputs(256);
+
+        let p = AffinePoint::<F>::new(p_x, p_y);
+        p.assert_on_curve(cs);
+
+        let out = ec_mul_windowed(p, &s_bits, cs);
+
+        cs.expose_public(out.x);
+        cs.expose_public(out.y);
+    }
+
+    #[test]
+    pub fn test_mul_windowed() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| mul_windowed_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        let p = (Secp256k1Affine::generator() * Fr::from(7u32)).into_affine();
+        let cases = [
+            Fr::from(3u32),
+            Fr::from(0u32),
+            Fr::from(1u32),
+            -Fr::from(1u32),
+        ];
+
+        for s in cases {
+            let s_bits = s
+                .into_bigint()
+                .to_bits_le()
+                .iter()
+                .map(|b| Fp::from(*b))
+                .collect::<Vec<Fp>>();
+
+            let out = (p * s).into_affine();
+
+            let pub_input = vec![out.x, out.y];
+            let mut priv_input = vec![p.x, p.y];
+            priv_input.extend_from_slice(&s_bits);
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
+
+    #[test]
+    pub fn bench_mul_windowed_num_constraints() {
+        let mut naive_cs = ConstraintSystem::<Fp>::new();
+        naive_cs.set_constraints(&mul_circuit::<Fp>);
+        let naive = naive_cs.num_constraints.unwrap();
+
+        let mut windowed_cs = ConstraintSystem::<Fp>::new();
+        windowed_cs.set_constraints(&mul_windowed_circuit::<Fp>);
+        let windowed = windowed_cs.num_constraints.unwrap();
+
+        println!("Num constraints (ec_mul): {}", naive);
+        println!("Num constraints (ec_mul_windowed): {}", windowed);
+
+        assert!(windowed < naive);
+    }
 }
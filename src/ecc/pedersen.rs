@@ -0,0 +1,262 @@
+use super::hash_to_curve::hash_to_curve_native;
+use super::mul::ec_mul_fixed_base;
+use super::{add::ec_add_complete, AffinePoint, CurveParams, Secp256k1};
+use ark_ec::{AffineRepr, CurveGroup};
+use frontend::ark_secp256k1::{Affine as Secp256k1Affine, Fq, Fr};
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+use std::sync::Mutex;
+
+const GENERATORS_DOMAIN_SEPARATOR: &[u8] = b"PedersenCommitment-secp256k1";
+
+// hash_to_curve(i) for the indices derived so far.
+// The chip is usually constructed inside a synthesizer, which runs for every witness,
+// so each generator is only derived the first time it is needed.
+static HASHED_GENERATORS: Mutex<Vec<Secp256k1Affine>> = Mutex::new(Vec::new());
+
+// hash_to_curve(0), ..., hash_to_curve(n - 1)
+fn hashed_generators(n: usize) -> Vec<Secp256k1Affine> {
+    let mut generators = HASHED_GENERATORS.lock().unwrap();
+    for i in generators.len()..n {
+        generators.push(hash_to_curve_native(
+            &[Fq::from(i as u64)],
+            GENERATORS_DOMAIN_SEPARATOR,
+        ));
+    }
+
+    generators[..n].to_vec()
+}
+
+// Pedersen commitments sum(v_i * G_i) + r * H over secp256k1.
+// G_0 is the generator of secp256k1, so a single value is committed to as v * G + r * H.
+// The blinding generator H = hash_to_curve(0) and the other value generators G_i = hash_to_curve(i)
+// are nothing-up-my-sleeve points whose discrete logarithms are unknown.
+// All generators are constants, so the multiplications use precomputed window tables.
+#[derive(Clone)]
+pub struct PedersenCommitmentChip {
+    pub value_generators: Vec<Secp256k1Affine>,
+    pub blinding_generator: Secp256k1Affine,
+}
+
+impl PedersenCommitmentChip {
+    // Sets up the generators to commit to up to `num_values` values
+    pub fn new(num_values: usize) -> Self {
+        let n = num_values.max(1);
+        let hashed = hashed_generators(n);
+
+        let mut value_generators = Vec::with_capacity(n);
+        value_generators.push(Secp256k1Affine::generator());
+        value_generators.extend_from_slice(&hashed[1..n]);
+
+        Self {
+            value_generators,
+            blinding_generator: hashed[0],
+        }
+    }
+
+    // v * G + r * H. The scalar bits are interpreted as LSB first.
+    pub fn commit<F: FieldGC>(
+        &self,
+        v_bits: &[Wire<F>],
+        r_bits: &[Wire<F>],
+        cs: &mut ConstraintSystem<F>,
+    ) -> AffinePoint<F>
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        self.commit_vector(&[v_bits.to_vec()], r_bits, cs)
+    }
+
+    // sum(v_i * G_i) + r * H. The scalar bits are interpreted as LSB first.
+    pub fn commit_vector<F: FieldGC>(
+        &self,
+        values_bits: &[Vec<Wire<F>>],
+        r_bits: &[Wire<F>],
+        cs: &mut ConstraintSystem<F>,
+    ) -> AffinePoint<F>
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        assert!(
+            values_bits.len() <= self.value_generators.len(),
+            "not enough generators"
+        );
+
+        let mut result = ec_mul_fixed_base(self.blinding_generator, r_bits, cs);
+        for (g, v_bits) in self.value_generators.iter().zip(values_bits.iter()) {
+            let term = ec_mul_fixed_base(*g, v_bits, cs);
+            result = ec_add_complete(term, result, cs);
+        }
+
+        result
+    }
+
+    // Constrains `commitment` to open to the values with the blinding factor r
+    pub fn open<F: FieldGC>(
+        &self,
+        commitment: AffinePoint<F>,
+        values_bits: &[Vec<Wire<F>>],
+        r_bits: &[Wire<F>],
+        cs: &mut ConstraintSystem<F>,
+    ) where
+        Secp256k1: CurveParams<F>,
+    {
+        let expected = self.commit_vector(values_bits, r_bits, cs);
+        cs.assert_equal(commitment.x, expected.x, "commitment mismatch");
+        cs.assert_equal(commitment.y, expected.y, "commitment mismatch");
+    }
+
+    // Native counterpart of `commit`
+    pub fn commit_native(&self, v: Fr, r: Fr) -> Secp256k1Affine {
+        self.commit_vector_native(&[v], r)
+    }
+
+    // Native counterpart of `commit_vector`
+    pub fn commit_vector_native(&self, values: &[Fr], r: Fr) -> Secp256k1Affine {
+        assert!(
+            values.len() <= self.value_generators.len(),
+            "not enough generators"
+        );
+
+        let mut result = self.blinding_generator * r;
+        for (g, v) in self.value_generators.iter().zip(values.iter()) {
+            result += *g * v;
+        }
+
+        result.into_affine()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use frontend::ark_ff::{BigInteger, PrimeField};
+
+    use super::*;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    const NUM_VALUES: usize = 3;
+
+    fn to_bits(s: Fr) -> Vec<Fp> {
+        s.into_bigint()
+            .to_bits_le()
+            .iter()
+            .map(|b| Fp::from(*b))
+            .collect()
+    }
+
+    #[test]
+    fn test_generators() {
+        let hash = |i: u64| hash_to_curve_native(&[Fq::from(i)], GENERATORS_DOMAIN_SEPARATOR);
+
+        // The derived generators don't depend on the order in which chips are constructed
+        for num_values in [2, 1, 5, 3] {
+            let pedersen = PedersenCommitmentChip::new(num_values);
+            assert_eq!(pedersen.value_generators.len(), num_values);
+            assert_eq!(pedersen.value_generators[0], Secp256k1Affine::generator());
+            assert_eq!(pedersen.blinding_generator, hash(0));
+            for (i, g) in pedersen.value_generators.iter().enumerate().skip(1) {
+                assert_eq!(*g, hash(i as u64));
+            }
+        }
+
+        let pedersen = PedersenCommitmentChip::new(5);
+        let mut generators = pedersen.value_generators.clone();
+        generators.push(pedersen.blinding_generator);
+        for (i, g) in generators.iter().enumerate() {
+            assert!(g.is_on_curve() && !g.is_zero());
+            assert!(generators[..i].iter().all(|h| h.x != g.x));
+        }
+    }
+
+    fn commit_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let v_bits = cs.alloc_priv_inputs(256);
+        let r_bits = cs.alloc_priv_inputs(256);
+
+        let pedersen = PedersenCommitmentChip::new(1);
+        let commitment = pedersen.commit(&v_bits, &r_bits, cs);
+
+        cs.expose_public(commitment.x);
+        cs.expose_public(commitment.y);
+    }
+
+    #[test]
+    fn test_commit() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| commit_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+
+        let pedersen = PedersenCommitmentChip::new(1);
+        let cases = [
+            (Fr::from(42u32), Fr::from(123456789u64)),
+            (Fr::from(0u32), -Fr::from(1u32)),
+        ];
+
+        for (v, r) in cases {
+            let expected = pedersen.commit_native(v, r);
+            assert_eq!(
+                expected,
+                (Secp256k1Affine::generator() * v + pedersen.blinding_generator * r).into_affine()
+            );
+
+            let mut priv_input = to_bits(v);
+            priv_input.extend(to_bits(r));
+            let pub_input = [expected.x, expected.y];
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
+
+    fn open_vector_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let c_x = cs.alloc_priv_input();
+        let c_y = cs.alloc_priv_input();
+        let values_bits = (0..NUM_VALUES)
+            .map(|_| cs.alloc_priv_inputs(256))
+            .collect::<Vec<Vec<Wire<F>>>>();
+        let r_bits = cs.alloc_priv_inputs(256);
+
+        let commitment = AffinePoint::<F>::new(c_x, c_y);
+
+        let pedersen = PedersenCommitmentChip::new(NUM_VALUES);
+        pedersen.open(commitment, &values_bits, &r_bits, cs);
+
+        cs.expose_public(commitment.x);
+        cs.expose_public(commitment.y);
+    }
+
+    #[test]
+    fn test_open_vector() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| open_vector_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+
+        let pedersen = PedersenCommitmentChip::new(NUM_VALUES);
+        let values = [Fr::from(1u32), Fr::from(0u32), -Fr::from(7u32)];
+        let r = Fr::from(987654321u64);
+
+        let commitment = pedersen.commit_vector_native(&values, r);
+
+        let mut priv_input = vec![commitment.x, commitment.y];
+        for v in values {
+            priv_input.extend(to_bits(v));
+        }
+        priv_input.extend(to_bits(r));
+        let pub_input = [commitment.x, commitment.y];
+
+        let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+        assert!(cs.is_sat(&witness, &pub_input));
+    }
+}
@@ -0,0 +1,273 @@
+use super::hash_to_curve::{hash_to_curve, hash_to_curve_native};
+use super::mul::{ec_mul, ec_mul_fixed_base};
+use super::{add::ec_add_complete, secp256k1_order, to_field, AffinePoint, CurveParams, Secp256k1};
+use crate::bitops::{is_less_than_const, to_bits_le};
+use crate::PoseidonSpongeChip;
+use ark_ec::{AffineRepr, CurveGroup};
+use frontend::ark_secp256k1::{Affine as Secp256k1Affine, Fq, Fr};
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+use num_bigint::BigUint;
+use shockwave_plus::{IOPattern, PoseidonCurve, PoseidonSponge, SpongeOp};
+
+// Deterministic nullifiers as in PLUME (v1); https://eprint.iacr.org/2022/1255
+// For the key pair (sk, pk = sk * G) and the message m,
+// H = hash_to_curve(m, pk) and the nullifier is N = sk * H.
+// The proof that N is well formed is a Chaum-Pedersen proof (c, s) with
+// c = hash(G, pk, H, N, r * G, r * H) and s = r + sk * c mod n.
+// Both hashes are Poseidon based, so the verification doesn't need SHA-256 in the circuit.
+
+const HASH_TO_CURVE_DOMAIN_SEPARATOR: &[u8] = b"PLUME-secp256k1-hash-to-curve";
+const CHALLENGE_DOMAIN_SEPARATOR: &[u8] = b"PLUME-secp256k1-challenge";
+
+const SPONGE_WIDTH: usize = 3;
+// G, pk, H, N, r * G and r * H
+const CHALLENGE_NUM_POINTS: usize = 6;
+
+fn challenge_io_pattern() -> IOPattern {
+    IOPattern::new(vec![
+        SpongeOp::Absorb(CHALLENGE_NUM_POINTS * 2),
+        SpongeOp::Squeeze(1),
+    ])
+}
+
+// Native counterpart of the challenge computed in `plume_verify`
+fn challenge_native(points: &[Secp256k1Affine; CHALLENGE_NUM_POINTS]) -> Fq {
+    let mut sponge = PoseidonSponge::<Fq, SPONGE_WIDTH>::new(
+        CHALLENGE_DOMAIN_SEPARATOR,
+        PoseidonCurve::SECP256K1,
+        challenge_io_pattern(),
+    );
+
+    let coords = points.iter().flat_map(|p| [p.x, p.y]).collect::<Vec<Fq>>();
+    sponge.absorb(&coords);
+
+    sponge.squeeze(1)[0]
+}
+
+// Computes the nullifier of the message and its proof (c, s) with the nonce r.
+// Returns (N, c, s), where c is a base field element that is used as an integer.
+pub fn plume_sign_native(msg: &[Fq], priv_key: Fr, r: Fr) -> (Secp256k1Affine, Fq, Fr) {
+    let g = Secp256k1Affine::generator();
+    let pub_key = (g * priv_key).into_affine();
+
+    let h = hash_to_curve_native(
+        &[msg, &[pub_key.x, pub_key.y]].concat(),
+        HASH_TO_CURVE_DOMAIN_SEPARATOR,
+    );
+    let nullifier = (h * priv_key).into_affine();
+
+    let g_r = (g * r).into_affine();
+    let h_r = (h * r).into_affine();
+
+    let c = challenge_native(&[g, pub_key, h, nullifier, g_r, h_r]);
+    let s = r + priv_key * to_field::<Fq, Fr>(c);
+
+    (nullifier, c, s)
+}
+
+// Verifies that `nullifier` is the PLUME nullifier of `msg` under `pub_key`,
+// given the proof (c, s).
+// r * G and r * H are recovered as s * G - c * pk and s * H - c * N,
+// and the challenge is recomputed from them.
+pub fn plume_verify<F: FieldGC>(
+    msg: &[Wire<F>],
+    pub_key: AffinePoint<F>,
+    nullifier: AffinePoint<F>,
+    c: Wire<F>,
+    s: Wire<F>,
+    cs: &mut ConstraintSystem<F>,
+) where
+    Secp256k1: CurveParams<F>,
+{
+    let one = cs.one();
+
+    let n = secp256k1_order();
+    let p: BigUint = F::MODULUS.into();
+
+    pub_key.assert_on_curve(cs);
+    nullifier.assert_on_curve(cs);
+
+    // The decomposition of c must be canonical, as c and c + p are different scalars
+    let c_bits = to_bits_le(c, 256);
+    cs.assert_equal(is_less_than_const(&c_bits, &p), one, "c is not less than p");
+
+    // s < n, so that the proof isn't malleable
+    let s_bits = to_bits_le(s, 256);
+    cs.assert_equal(is_less_than_const(&s_bits, &n), one, "s is not less than n");
+
+    let mut h_input = msg.to_vec();
+    h_input.extend_from_slice(&[pub_key.x, pub_key.y]);
+    let H = hash_to_curve(&h_input, HASH_TO_CURVE_DOMAIN_SEPARATOR, cs);
+
+    // r * G = s * G - c * pk
+    let sG = ec_mul_fixed_base(Secp256k1Affine::generator(), &s_bits, cs);
+    let c_pk = ec_mul(pub_key, &c_bits, cs);
+    let g_r = ec_add_complete(sG, AffinePoint::new(c_pk.x, -c_pk.y), cs);
+
+    // r * H = s * H - c * N
+    let sH = ec_mul(H, &s_bits, cs);
+    let c_nullifier = ec_mul(nullifier, &c_bits, cs);
+    let h_r = ec_add_complete(sH, AffinePoint::new(c_nullifier.x, -c_nullifier.y), cs);
+
+    let G = AffinePoint::<F>::generator(cs);
+
+    let mut sponge = PoseidonSpongeChip::<F, SPONGE_WIDTH>::new(
+        CHALLENGE_DOMAIN_SEPARATOR,
+        challenge_io_pattern(),
+        PoseidonCurve::SECP256K1,
+        cs,
+    );
+    let coords = [G, pub_key, H, nullifier, g_r, h_r]
+        .iter()
+        .flat_map(|p| [p.x, p.y])
+        .collect::<Vec<Wire<F>>>();
+    sponge.absorb(&coords);
+    let expected_c = sponge.squeeze(1)[0];
+
+    cs.assert_equal(c, expected_c, "challenge mismatch");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::is_rejected;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    const MSG_LEN: usize = 2;
+
+    // The messages of the assertions of plume_verify
+    const ASSERTIONS: [&str; 3] = [
+        "c is not less than p",
+        "s is not less than n",
+        "challenge mismatch",
+    ];
+
+    fn plume_verify_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let msg = cs.alloc_priv_inputs(MSG_LEN);
+
+        let pub_key_x = cs.alloc_priv_input();
+        let pub_key_y = cs.alloc_priv_input();
+        let pub_key = AffinePoint::<F>::new(pub_key_x, pub_key_y);
+
+        let nullifier_x = cs.alloc_priv_input();
+        let nullifier_y = cs.alloc_priv_input();
+        let nullifier = AffinePoint::<F>::new(nullifier_x, nullifier_y);
+
+        let c = cs.alloc_priv_input();
+        let s = cs.alloc_priv_input();
+
+        plume_verify(&msg, pub_key, nullifier, c, s, cs);
+
+        cs.expose_public(nullifier.x);
+        cs.expose_public(nullifier.y);
+    }
+
+    fn to_inputs(
+        msg: &[Fp],
+        pub_key: Secp256k1Affine,
+        nullifier: Secp256k1Affine,
+        c: Fp,
+        s: Fr,
+    ) -> (Vec<Fp>, [Fp; 2]) {
+        let mut priv_input = msg.to_vec();
+        priv_input.extend_from_slice(&[
+            pub_key.x,
+            pub_key.y,
+            nullifier.x,
+            nullifier.y,
+            c,
+            to_field::<Fr, Fp>(s),
+        ]);
+        let pub_input = [nullifier.x, nullifier.y];
+
+        (priv_input, pub_input)
+    }
+
+    #[test]
+    fn test_plume_verify() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| plume_verify_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+
+        let priv_key = Fr::from(123456789u64);
+        let pub_key = (Secp256k1Affine::generator() * priv_key).into_affine();
+        let msg = [Fp::from(42u32), Fp::from(7u32)];
+
+        // The nullifier doesn't depend on the nonce
+        let (nullifier, _, _) = plume_sign_native(&msg, priv_key, Fr::from(1u32));
+
+        for r in [Fr::from(987654321u64), -Fr::from(5u32)] {
+            let (nullifier_r, c, s) = plume_sign_native(&msg, priv_key, r);
+            assert_eq!(nullifier, nullifier_r);
+
+            let (priv_input, pub_input) = to_inputs(&msg, pub_key, nullifier, c, s);
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
+
+    #[test]
+    fn test_plume_verify_rejects() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| plume_verify_circuit(cs);
+
+        let priv_key = Fr::from(123456789u64);
+        let pub_key = (Secp256k1Affine::generator() * priv_key).into_affine();
+        let msg = [Fp::from(42u32), Fp::from(7u32)];
+        let r = Fr::from(987654321u64);
+
+        let (nullifier, c, s) = plume_sign_native(&msg, priv_key, r);
+
+        let (priv_input, pub_input) = to_inputs(&msg, pub_key, nullifier, c, s);
+        assert!(!is_rejected(
+            synthesizer,
+            &pub_input,
+            &priv_input,
+            &ASSERTIONS
+        ));
+
+        // The nullifier and the public key of another key pair
+        let other_key = Fr::from(987654321u64);
+        let other_pub_key = (Secp256k1Affine::generator() * other_key).into_affine();
+        let (other_nullifier, _, _) = plume_sign_native(&msg, other_key, r);
+
+        let cases = [
+            (
+                "wrong nullifier",
+                to_inputs(&msg, pub_key, other_nullifier, c, s),
+            ),
+            (
+                "tampered c",
+                to_inputs(&msg, pub_key, nullifier, c + Fp::from(1u32), s),
+            ),
+            (
+                "tampered s",
+                to_inputs(&msg, pub_key, nullifier, c, s + Fr::from(1u32)),
+            ),
+            (
+                "different public key",
+                to_inputs(&msg, other_pub_key, nullifier, c, s),
+            ),
+            (
+                "different message",
+                to_inputs(&[msg[0], msg[0]], pub_key, nullifier, c, s),
+            ),
+        ];
+
+        for (name, (priv_input, pub_input)) in cases {
+            assert!(
+                is_rejected(synthesizer, &pub_input, &priv_input, &ASSERTIONS),
+                "{} accepted",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use super::{AffinePoint, CurveParams, Secp256k1};
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+use std::marker::PhantomData;
+
+// A point in homogeneous projective coordinates (X : Y : Z), representing (X / Z, Y / Z).
+// The identity is (0 : 1 : 0).
+// The projective formulas are only implemented for curves with a = 0,
+// so constructing a point on any other curve panics.
+#[derive(Copy, Clone)]
+pub struct ProjectivePoint<F: FieldGC, C: CurveParams<F> = Secp256k1> {
+    pub x: Wire<F>,
+    pub y: Wire<F>,
+    pub z: Wire<F>,
+    _curve: PhantomData<C>,
+}
+
+impl<F: FieldGC, C: CurveParams<F>> ProjectivePoint<F, C> {
+    pub fn new(x: Wire<F>, y: Wire<F>, z: Wire<F>) -> Self {
+        assert!(
+            C::a() == F::ZERO,
+            "projective formulas are only implemented for a = 0"
+        );
+
+        Self {
+            x,
+            y,
+            z,
+            _curve: PhantomData,
+        }
+    }
+
+    // Maps the affine identity (0, 0) to (0 : 1 : 0), and any other point to (x : y : 1)
+    pub fn from_affine(p: AffinePoint<F, C>, cs: &mut ConstraintSystem<F>) -> Self {
+        let is_zero = p.x.is_zero();
+        let one = cs.one();
+
+        let y = cs.if_then(is_zero, one).else_then(p.y);
+        let z = one - is_zero;
+
+        Self::new(p.x, y, z)
+    }
+
+    // (X / Z, Y / Z), where the identity (Z = 0) is mapped to (0, 0)
+    pub fn to_affine(&self) -> AffinePoint<F, C> {
+        let x = self.x.div_or_zero(self.z);
+        let y = self.y.div_or_zero(self.z);
+
+        AffinePoint::new(x, y)
+    }
+}
+
+// 3 * b. The formulas below assume a = 0, which `ProjectivePoint::new` enforces.
+fn b3<F: FieldGC, C: CurveParams<F>>() -> F {
+    C::b() * F::from(3u32)
+}
+
+// Complete addition for short-Weierstrass curves with a = 0.
+// We follow Algorithm 7 of Renes-Costello-Batina 2015;
+// https://eprint.iacr.org/2015/1060.pdf
+// No inversions are needed, and the formulas have no exceptional cases.
+pub fn ec_add_projective<F: FieldGC, C: CurveParams<F>>(
+    p: ProjectivePoint<F, C>,
+    q: ProjectivePoint<F, C>,
+    cs: &mut ConstraintSystem<F>,
+) -> ProjectivePoint<F, C> {
+    let b3 = b3::<F, C>();
+
+    let t0 = p.x * q.x;
+    let t1 = p.y * q.y;
+    let t2 = p.z * q.z;
+
+    // t3 = (X1 + Y1) * (X2 + Y2) - t0 - t1
+    let t3 = cs.deg_2_comb(
+        &[(p.x, F::ONE), (p.y, F::ONE)],
+        &[(q.x, F::ONE), (q.y, F::ONE)],
+        &[(t0, -F::ONE), (t1, -F::ONE)],
+    );
+    // t4 = (Y1 + Z1) * (Y2 + Z2) - t1 - t2
+    let t4 = cs.deg_2_comb(
+        &[(p.y, F::ONE), (p.z, F::ONE)],
+        &[(q.y, F::ONE), (q.z, F::ONE)],
+        &[(t1, -F::ONE), (t2, -F::ONE)],
+    );
+    // t5 = (X1 + Z1) * (X2 + Z2) - t0 - t2
+    let t5 = cs.deg_2_comb(
+        &[(p.x, F::ONE), (p.z, F::ONE)],
+        &[(q.x, F::ONE), (q.z, F::ONE)],
+        &[(t0, -F::ONE), (t2, -F::ONE)],
+    );
+
+    // X3 = t3 * (t1 - b3 * t2) - t4 * b3 * t5
+    let t4_t5 = cs.deg_2_comb(&[(t4, F::ONE)], &[(t5, b3)], &[]);
+    let out_x = cs.deg_2_comb(
+        &[(t3, F::ONE)],
+        &[(t1, F::ONE), (t2, -b3)],
+        &[(t4_t5, -F::ONE)],
+    );
+
+    // Y3 = (t1 - b3 * t2) * (t1 + b3 * t2) + b3 * t5 * 3 * t0
+    let t5_t0 = cs.deg_2_comb(&[(t5, b3)], &[(t0, F::from(3u32))], &[]);
+    let out_y = cs.deg_2_comb(
+        &[(t1, F::ONE), (t2, -b3)],
+        &[(t1, F::ONE), (t2, b3)],
+        &[(t5_t0, F::ONE)],
+    );
+
+    // Z3 = (t1 + b3 * t2) * t4 + 3 * t0 * t3
+    let t0_t3 = cs.deg_2_comb(&[(t0, F::from(3u32))], &[(t3, F::ONE)], &[]);
+    let out_z = cs.deg_2_comb(
+        &[(t1, F::ONE), (t2, b3)],
+        &[(t4, F::ONE)],
+        &[(t0_t3, F::ONE)],
+    );
+
+    ProjectivePoint::new(out_x, out_y, out_z)
+}
+
+// Doubling for short-Weierstrass curves with a = 0.
+// We follow Algorithm 9 of Renes-Costello-Batina 2015.
+pub fn ec_double_projective<F: FieldGC, C: CurveParams<F>>(
+    p: ProjectivePoint<F, C>,
+    cs: &mut ConstraintSystem<F>,
+) -> ProjectivePoint<F, C> {
+    let b3 = b3::<F, C>();
+    let three_b3 = b3 * F::from(3u32);
+
+    let t0 = p.y * p.y;
+    let t1 = p.y * p.z;
+    let zz = p.z * p.z;
+    let xy = p.x * p.y;
+
+    // b3 * Z^2 * 8 * t0
+    let t2_z3 = cs.deg_2_comb(&[(zz, b3)], &[(t0, F::from(8u32))], &[]);
+
+    // X3 = 2 * (t0 - 3 * b3 * Z^2) * X * Y
+    let out_x = cs.deg_2_comb(
+        &[(t0, F::from(2u32)), (zz, -three_b3 * F::from(2u32))],
+        &[(xy, F::ONE)],
+        &[],
+    );
+    // Y3 = (t0 - 3 * b3 * Z^2) * (t0 + b3 * Z^2) + b3 * Z^2 * 8 * t0
+    let out_y = cs.deg_2_comb(
+        &[(t0, F::ONE), (zz, -three_b3)],
+        &[(t0, F::ONE), (zz, b3)],
+        &[(t2_z3, F::ONE)],
+    );
+    // Z3 = 8 * t0 * Y * Z
+    let out_z = cs.deg_2_comb(&[(t1, F::ONE)], &[(t0, F::from(8u32))], &[]);
+
+    ProjectivePoint::new(out_x, out_y, out_z)
+}
+
+// Double-and-add in projective coordinates.
+// Only the final conversion back to affine coordinates requires a division.
+pub fn ec_mul_projective<F: FieldGC, C: CurveParams<F>>(
+    p: AffinePoint<F, C>,
+    s_bits: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) -> AffinePoint<F, C> {
+    let zero = cs.alloc_const(F::ZERO);
+    let one = cs.one();
+
+    let mut result = ProjectivePoint::new(zero, one, zero);
+    let mut current = ProjectivePoint::from_affine(p, cs);
+
+    for s_i in s_bits {
+        // s_i * current, where 0 * current = (0 : 1 : 0)
+        let t_x = *s_i * current.x;
+        let t_y = cs.deg_2_comb(
+            &[(*s_i, F::ONE)],
+            &[(current.y, F::ONE), (one, -F::ONE)],
+            &[(one, F::ONE)],
+        );
+        let t_z = *s_i * current.z;
+        let t = ProjectivePoint::new(t_x, t_y, t_z);
+
+        result = ec_add_projective(t, result, cs);
+        current = ec_double_projective(current, cs);
+    }
+
+    result.to_affine()
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ec::{AffineRepr, CurveGroup};
+    use frontend::ark_ff::{BigInteger, PrimeField};
+    use frontend::ark_secp256k1::Affine as Secp256k1Affine;
+    use frontend::ark_secp256k1::Fr;
+
+    use super::*;
+    use crate::ec_mul;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    fn add_projective_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let p_x = cs.alloc_priv_input();
+        let p_y = cs.alloc_priv_input();
+        let q_x = cs.alloc_priv_input();
+        let q_y = cs.alloc_priv_input();
+
+        let p = AffinePoint::<F>::new(p_x, p_y);
+        let q = AffinePoint::<F>::new(q_x, q_y);
+        p.assert_on_curve_or_identity(cs);
+        q.assert_on_curve_or_identity(cs);
+
+        let p = ProjectivePoint::from_affine(p, cs);
+        let q = ProjectivePoint::from_affine(q, cs);
+
+        let sum = ec_add_projective(p, q, cs).to_affine();
+        let doubled = ec_double_projective(p, cs).to_affine();
+
+        cs.expose_public(sum.x);
+        cs.expose_public(sum.y);
+        cs.expose_public(doubled.x);
+        cs.expose_public(doubled.y);
+    }
+
+    #[test]
+    fn test_add_projective() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| add_projective_circuit(cs);
+
+        let zero = Secp256k1Affine::identity();
+        let p_nonzero = (Secp256k1Affine::generator() * Fr::from(124221521521u64)).into_affine();
+        let q_nonzero = (Secp256k1Affine::generator() * Fr::from(11321153521u64)).into_affine();
+
+        let cases = [
+            (zero, zero),
+            (zero, p_nonzero),
+            (p_nonzero, zero),
+            (p_nonzero, -p_nonzero),
+            (p_nonzero, p_nonzero),
+            (p_nonzero, q_nonzero),
+        ];
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        for (p, q) in cases {
+            let sum = (p + q).into_affine();
+            let doubled = (p + p).into_affine();
+
+            let pub_input = vec![sum.x, sum.y, doubled.x, doubled.y];
+            let priv_input = vec![p.x, p.y, q.x, q.y];
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
+
+    fn mul_projective_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let p_x = cs.alloc_priv_input();
+        let p_y = cs.alloc_priv_input();
+        let s_bits = cs.alloc_priv_inputs(256);
+
+        let p = AffinePoint::<F>::new(p_x, p_y);
+        p.assert_on_curve(cs);
+
+        let out = ec_mul_projective(p, &s_bits, cs);
+
+        cs.expose_public(out.x);
+        cs.expose_public(out.y);
+    }
+
+    fn mul_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let p_x = cs.alloc_priv_input();
+        let p_y = cs.alloc_priv_input();
+        let s_bits = cs.alloc_priv_inputs(256);
+
+        let out = ec_mul(AffinePoint::<F>::new(p_x, p_y), &s_bits, cs);
+
+        cs.expose_public(out.x);
+        cs.expose_public(out.y);
+    }
+
+    #[test]
+    fn test_mul_projective() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| mul_projective_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        let p = (Secp256k1Affine::generator() * Fr::from(7u32)).into_affine();
+        let cases = [Fr::from(3u32), Fr::from(0u32), -Fr::from(1u32)];
+
+        for s in cases {
+            let s_bits = s
+                .into_bigint()
+                .to_bits_le()
+                .iter()
+                .map(|b| Fp::from(*b))
+                .collect::<Vec<Fp>>();
+
+            let out = (p * s).into_affine();
+
+            let pub_input = vec![out.x, out.y];
+            let mut priv_input = vec![p.x, p.y];
+            priv_input.extend_from_slice(&s_bits);
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+
+        let mut affine_cs = ConstraintSystem::<Fp>::new();
+        affine_cs.set_constraints(&mul_circuit::<Fp>);
+
+        println!(
+            "Num constraints (ec_mul): {}",
+            affine_cs.num_constraints.unwrap()
+        );
+        println!(
+            "Num constraints (ec_mul_projective): {}",
+            cs.num_constraints.unwrap()
+        );
+
+        assert!(cs.num_constraints.unwrap() < affine_cs.num_constraints.unwrap());
+    }
+}
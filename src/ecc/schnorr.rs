@@ -0,0 +1,282 @@
+use super::compress::{ec_decompress, parity};
+use super::mul::{ec_mul, ec_mul_fixed_base};
+use super::{add::ec_add_complete, secp256k1_order, AffinePoint, CurveParams, Secp256k1};
+use crate::bitops::{is_less_than_const, to_bits_le};
+use crate::sha256::sha256_from_state;
+use ark_ec::AffineRepr;
+use frontend::ark_secp256k1::Affine as Secp256k1Affine;
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+use num_bigint::BigUint;
+
+// The SHA-256 midstate after absorbing SHA256("BIP0340/challenge") || SHA256("BIP0340/challenge"),
+// which is the constant 64-byte prefix of the challenge tagged hash.
+const CHALLENGE_MIDSTATE: [u32; 8] = [
+    0x9cecba11, 0x23925381, 0x11679112, 0xd1627e0f, 0x97c87550, 0x003cc765, 0x90f61164, 0x33e9b66a,
+];
+const CHALLENGE_PREFIX_BYTES: usize = 64;
+
+// Verifies a BIP-340 Schnorr signature (r, s) of the 32-byte message under the x-only public key;
+// https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki
+// `msg_bits` are the bits of the message bytes, MSB first, and are assumed to be already
+// constrained to be boolean.
+// The public key is lifted to the point with an even y coordinate, and the signature is valid iff
+// R = s * G - e * P is not the identity, has an even y coordinate and R.x = r,
+// where e = int(hash_BIP0340/challenge(r || P.x || m)) mod n.
+pub fn schnorr_verify<F: FieldGC>(
+    pub_key_x: Wire<F>,
+    r: Wire<F>,
+    s: Wire<F>,
+    msg_bits: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) where
+    Secp256k1: CurveParams<F>,
+{
+    assert_eq!(msg_bits.len(), 256);
+
+    let zero = cs.alloc_const(F::ZERO);
+    let one = cs.one();
+
+    let n = secp256k1_order();
+    let p: BigUint = F::MODULUS.into();
+
+    // lift_x: fails if x is not on the curve
+    let P = ec_decompress::<F, Secp256k1>(pub_key_x, zero, cs);
+
+    // The decompositions of r and P.x are hashed, so they must be canonical
+    let r_bits = to_bits_le(r, 256);
+    let pub_key_x_bits = to_bits_le(pub_key_x, 256);
+    let s_bits = to_bits_le(s, 256);
+
+    cs.assert_equal(is_less_than_const(&r_bits, &p), one, "r is not less than p");
+    cs.assert_equal(
+        is_less_than_const(&pub_key_x_bits, &p),
+        one,
+        "P.x is not less than p",
+    );
+    cs.assert_equal(is_less_than_const(&s_bits, &n), one, "s is not less than n");
+
+    // The hash input is the big-endian encoding of r and P.x, followed by the message
+    let mut challenge_input = Vec::with_capacity(768);
+    challenge_input.extend(r_bits.iter().rev());
+    challenge_input.extend(pub_key_x_bits.iter().rev());
+    challenge_input.extend_from_slice(msg_bits);
+
+    let e_bits = sha256_from_state(CHALLENGE_MIDSTATE, CHALLENGE_PREFIX_BYTES, &challenge_input);
+
+    // The digest is interpreted as a big-endian integer.
+    // e * P is the same as (e mod n) * P, so the reduction is implicit.
+    let e_bits = e_bits.into_iter().rev().collect::<Vec<Wire<F>>>();
+
+    // R = s * G - e * P
+    let sG = ec_mul_fixed_base(Secp256k1Affine::generator(), &s_bits, cs);
+    let eP = ec_mul(P, &e_bits, cs);
+    let R = ec_add_complete(sG, AffinePoint::new(eP.x, -eP.y), cs);
+
+    // No point on the curve has x = 0, so R.x = 0 iff R is the identity
+    cs.assert_equal(R.x.is_zero(), zero, "R is the identity");
+    cs.assert_equal(parity(R.y, cs), zero, "R.y is odd");
+    cs.assert_equal(R.x, r, "R.x != r");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::is_rejected;
+    use frontend::ark_ff::PrimeField;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    // Valid signatures from the BIP-340 test vectors: (public key, message, signature)
+    const TEST_VECTORS: [[&str; 3]; 5] = [
+        [
+            "F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "E907831F80848D1069A5371B402410364BDF1C5F8307B0084C55F1CE2DCA821525F66A4A85EA8B71E482A74F382D2CE5EBEEE8FDB2172F477DF4900D310536C0",
+        ],
+        [
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6896BD60EEAE296DB48A229FF71DFE071BDE413E6D43F917DC8DCF8C78DE33418906D11AC976ABCCB20B091292BFF4EA897EFCB639EA871CFA95F6DE339E4B0A",
+        ],
+        [
+            "DD308AFEC5777E13121FA72B9CC1B7CC0139715309B086C960E18FD969774EB8",
+            "7E2D58D8B3BCDF1ABADEC7829054F90DDA9805AAB56C77333024B9D0A508B75C",
+            "5831AAEED7B44BB74E5EAB94BA9D4294C49BCF2A60728D8B4C200F50DD313C1BAB745879A5AD954A72C45A91C3A51D3C7ADEA98D82F8481E0E1E03674A6F3FB7",
+        ],
+        [
+            "25D1DFF95105F5253C4022F628A996AD3A0D95FBF21D468A1B33F8C160D8F517",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+            "7EB0509757E246F19449885651611CB965ECC1A187DD51B64FDA1EDC9637D5EC97582B9CB13DB3933705B32BA982AF5AF25FD78881EBB32771FC5922EFC66EA3",
+        ],
+        [
+            "D69C3509BB99E412E68B0FE8544E72837DFA30746D8BE2AA65975F29D22DC7B9",
+            "4DF3C3F68FCC83B27E9D42C90431A72499F17875C81A599B566C9889B9696703",
+            "00000000000000000000003B78CE563F89A0ED9414F5AA28AD0D96D6795F9C6376AFB1548AF603B3EB45C9F8207DEE1060CB71C04E80F593060B07D28308D7F4",
+        ],
+    ];
+
+    // Invalid signatures from the BIP-340 test vectors, starting from index 5
+    const INVALID_TEST_VECTORS: [[&str; 3]; 10] = [
+        // 5. public key not on the curve
+        [
+            "EEFDEA4CDB677750A420FEE807EACF21EB9898AE79B9768766E4FAA04A2D4A34",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6CFF5C3BA86C69EA4B7376F31A9BCB4F74C1976089B2D9963DA2E5543E17776969E89B4C5564D00349106B8497785DD7D1D713A8AE82B32FA79D5F7FC407D39B",
+        ],
+        // 6. R has an odd y coordinate
+        [
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "FFF97BD5755EEEA420453A14355235D382F6472F8568A18B2F057A14602975563CC27944640AC607CD107AE10923D9EF7A73C643E166BE5EBEAFA34B1AC553E2",
+        ],
+        // 7. negated message
+        [
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "1FA62E331EDBC21C394792D2AB1100A7B432B013DF3F6FF4F99FCB33E0E1515F28890B3EDB6E7189B630448B515CE4F8622A954CFE545735AAEA5134FCCDB2BD",
+        ],
+        // 8. negated s
+        [
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6CFF5C3BA86C69EA4B7376F31A9BCB4F74C1976089B2D9963DA2E5543E177769961764B3AA9B2FFCB6EF947B6887A226E8D7C93E00C5ED0C1834FF0D0C2E6DA6",
+        ],
+        // 9. s * G - e * P is the identity
+        [
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "0000000000000000000000000000000000000000000000000000000000000000123DDA8328AF9C23A94C1FEECFD123BA4FB73476F0D594DCB65C6425BD186051",
+        ],
+        // 10. s * G - e * P is the identity, with r = 1
+        [
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "00000000000000000000000000000000000000000000000000000000000000017615FBAF5AE28864013C099742DEADB4DBA87F11AC6754F93780D5A1837CF197",
+        ],
+        // 11. r is not the x coordinate of a point on the curve
+        [
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "4A298DACAE57395A15D0795DDBFD1DCB564DA82B0F269BC70A74F8220429BA1D69E89B4C5564D00349106B8497785DD7D1D713A8AE82B32FA79D5F7FC407D39B",
+        ],
+        // 12. r is equal to p
+        [
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F69E89B4C5564D00349106B8497785DD7D1D713A8AE82B32FA79D5F7FC407D39B",
+        ],
+        // 13. s is equal to n
+        [
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6CFF5C3BA86C69EA4B7376F31A9BCB4F74C1976089B2D9963DA2E5543E177769FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141",
+        ],
+        // 14. public key is not less than p
+        [
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC30",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6CFF5C3BA86C69EA4B7376F31A9BCB4F74C1976089B2D9963DA2E5543E17776969E89B4C5564D00349106B8497785DD7D1D713A8AE82B32FA79D5F7FC407D39B",
+        ],
+    ];
+
+    // The messages of the assertions of schnorr_verify, including the lifting of the public key
+    const ASSERTIONS: [&str; 9] = [
+        "point is not on the curve",
+        "y is not in canonical form",
+        "parity of y mismatch",
+        "r is not less than p",
+        "P.x is not less than p",
+        "s is not less than n",
+        "R is the identity",
+        "R.y is odd",
+        "R.x != r",
+    ];
+
+    fn schnorr_verify_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>)
+    where
+        Secp256k1: CurveParams<F>,
+    {
+        let pub_key_x = cs.alloc_priv_input();
+        let r = cs.alloc_priv_input();
+        let s = cs.alloc_priv_input();
+        let msg_bits = cs.alloc_priv_inputs(256);
+
+        for bit in &msg_bits {
+            cs.assert_equal(*bit * *bit, *bit, "msg bit is not boolean");
+        }
+
+        schnorr_verify(pub_key_x, r, s, &msg_bits, cs);
+
+        cs.expose_public(pub_key_x);
+    }
+
+    // Returns None if the big-endian integer isn't less than p, so it can't be a field element
+    fn from_be_bytes(bytes: &[u8]) -> Option<Fp> {
+        let x = BigUint::from_bytes_be(bytes);
+        let p: BigUint = Fp::MODULUS.into();
+        if x < p {
+            Some(Fp::from(x))
+        } else {
+            None
+        }
+    }
+
+    // Encodes a test vector as the inputs of `schnorr_verify_circuit`.
+    // Returns None if the public key or r can't be encoded.
+    fn to_inputs(pub_key: &str, msg: &str, sig: &str) -> Option<(Vec<Fp>, [Fp; 1])> {
+        let pub_key = hex::decode(pub_key).unwrap();
+        let msg = hex::decode(msg).unwrap();
+        let sig = hex::decode(sig).unwrap();
+
+        let pub_key_x = from_be_bytes(&pub_key)?;
+
+        let mut priv_input = vec![
+            pub_key_x,
+            from_be_bytes(&sig[..32])?,
+            from_be_bytes(&sig[32..])?,
+        ];
+        priv_input.extend(
+            msg.iter()
+                .flat_map(|byte| (0..8).rev().map(move |i| Fp::from(byte >> i & 1 == 1))),
+        );
+
+        Some((priv_input, [pub_key_x]))
+    }
+
+    #[test]
+    fn test_schnorr_verify() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| schnorr_verify_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+
+        for [pub_key, msg, sig] in TEST_VECTORS {
+            let (priv_input, pub_input) = to_inputs(pub_key, msg, sig).unwrap();
+
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input));
+        }
+    }
+
+    #[test]
+    fn test_schnorr_verify_rejects() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| schnorr_verify_circuit(cs);
+
+        for (i, [pub_key, msg, sig]) in INVALID_TEST_VECTORS.into_iter().enumerate() {
+            let index = i + 5;
+
+            match to_inputs(pub_key, msg, sig) {
+                Some((priv_input, pub_input)) => assert!(
+                    is_rejected(synthesizer, &pub_input, &priv_input, &ASSERTIONS),
+                    "test vector {} accepted",
+                    index
+                ),
+                // r = p and P.x > p don't fit in the field, so they are rejected before proving
+                None => assert!([12, 14].contains(&index), "test vector {}", index),
+            }
+        }
+    }
+}
@@ -2,15 +2,37 @@
 mod bitops;
 mod ecc;
 mod poseidon;
+mod sha256;
+#[cfg(test)]
+mod test_utils;
 mod to_addr;
 mod tree;
 
-pub use bitops::{from_bits, to_bits};
+pub use bitops::{from_bits, is_less_than_const, to_bits, to_bits_le};
 pub use ecc::add::{ec_add_complete, ec_add_incomplete};
-pub use ecc::double::ec_double;
-pub use ecc::mul::ec_mul;
-pub use ecc::AffinePoint;
-pub use poseidon::poseidon::PoseidonChip;
-pub use poseidon::sponge::PoseidonSpongeChip;
+pub use ecc::compress::{ec_compress, ec_decompress};
+pub use ecc::double::{ec_double, ec_double_complete};
+pub use ecc::ecdsa::ecdsa_verify;
+pub use ecc::efficient_ecdsa::efficient_ecdsa;
+pub use ecc::glv::{ec_mul_glv, glv_decompose};
+pub use ecc::hash_to_curve::{
+    hash_to_curve, hash_to_curve_native, map_to_curve, map_to_curve_native,
+};
+pub use ecc::msm::ec_msm;
+pub use ecc::mul::{ec_mul, ec_mul_fixed_base, ec_mul_windowed};
+pub use ecc::pedersen::PedersenCommitmentChip;
+pub use ecc::plume::{plume_sign_native, plume_verify};
+pub use ecc::projective::{
+    ec_add_projective, ec_double_projective, ec_mul_projective, ProjectivePoint,
+};
+pub use ecc::schnorr::schnorr_verify;
+pub use ecc::{AffinePoint, CurveParams, Secp256k1, Secq256k1};
+pub use poseidon::hash::{
+    poseidon_hash, poseidon_hash_native, poseidon_hash_var_len, poseidon_hash_var_len_native,
+};
+pub use poseidon::poseidon::{OptimizedConstants, PoseidonChip};
+pub use poseidon::poseidon2::{Poseidon2, Poseidon2Chip, Poseidon2Constants};
+pub use poseidon::sponge::{poseidon_sponge_native, PoseidonSpongeChip};
+pub use sha256::{sha256, sha256_compress, sha256_from_state, SHA256_IV};
 pub use to_addr::to_addr;
-pub use tree::verify_merkle_proof;
+pub use tree::{verify_merkle_proof, MerkleTree};
@@ -0,0 +1,271 @@
+use frontend::{ConstraintSystem, FieldGC, Wire};
+use shockwave_plus::{IOPattern, PoseidonCurve, PoseidonSponge, SpongeOp};
+
+use super::sponge::{poseidon_sponge_native, PoseidonSpongeChip};
+
+// Hashes field elements into one.
+// The circuits and the native functions share the parameters below, so they always agree.
+// The sponge places its tag in the first element of the state, which the first absorbed
+// element overwrites. So the tag is also absorbed as the first element.
+// The tag commits to the domain separator and to the number of inputs through the IO pattern,
+// so hashes of different domains or of inputs of different lengths are separated,
+// and even a hash of no inputs goes through a permutation.
+
+const DOMAIN_SEPARATOR: &[u8] = b"poseidon_hash";
+const VAR_LEN_DOMAIN_SEPARATOR: &[u8] = b"poseidon_hash_var_len";
+const SPONGE_WIDTH: usize = 3;
+const SPONGE_CAPACITY: usize = 1;
+const CURVE: PoseidonCurve = PoseidonCurve::SECP256K1;
+
+// The tag and the inputs are absorbed
+fn io_pattern(num_inputs: usize) -> IOPattern {
+    IOPattern::new(vec![SpongeOp::Absorb(num_inputs + 1), SpongeOp::Squeeze(1)])
+}
+
+fn tag<F: FieldGC>(domain_separator: &[u8], num_inputs: usize) -> F {
+    PoseidonSponge::<F, SPONGE_WIDTH>::compute_tag(domain_separator, &io_pattern(num_inputs))
+}
+
+fn sponge_hash<F: FieldGC>(
+    domain_separator: &[u8],
+    inputs: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) -> Wire<F> {
+    let mut sponge = PoseidonSpongeChip::<F, SPONGE_WIDTH>::new_with_capacity(
+        domain_separator,
+        io_pattern(inputs.len()),
+        CURVE,
+        SPONGE_CAPACITY,
+        cs,
+    );
+
+    let mut elements = vec![cs.alloc_const(tag(domain_separator, inputs.len()))];
+    elements.extend_from_slice(inputs);
+
+    sponge.absorb(&elements);
+    sponge.squeeze(1)[0]
+}
+
+fn sponge_hash_native<F: FieldGC>(domain_separator: &[u8], inputs: &[F]) -> F {
+    let mut sponge = poseidon_sponge_native::<F, SPONGE_WIDTH>(
+        domain_separator,
+        io_pattern(inputs.len()),
+        CURVE,
+        SPONGE_CAPACITY,
+    );
+
+    let mut elements = vec![tag(domain_separator, inputs.len())];
+    elements.extend_from_slice(inputs);
+
+    sponge.absorb(&elements);
+    sponge.squeeze(1)[0]
+}
+
+pub fn poseidon_hash<F: FieldGC, const N: usize>(
+    inputs: &[Wire<F>; N],
+    cs: &mut ConstraintSystem<F>,
+) -> Wire<F> {
+    sponge_hash(DOMAIN_SEPARATOR, inputs, cs)
+}
+
+// Native counterpart of `poseidon_hash`
+pub fn poseidon_hash_native<F: FieldGC, const N: usize>(inputs: &[F; N]) -> F {
+    sponge_hash_native(DOMAIN_SEPARATOR, inputs)
+}
+
+// Hashes the first `len` elements of `inputs`, where `len` is a witness of at most MAX_LEN.
+// The message is padded with a one followed by zeros to MAX_LEN + 1 elements,
+// which is injective, and the padded message is hashed.
+// The elements of `inputs` from `len` on don't affect the hash.
+// The cost is that of hashing MAX_LEN + 1 elements, regardless of `len`.
+pub fn poseidon_hash_var_len<F: FieldGC, const MAX_LEN: usize>(
+    inputs: &[Wire<F>; MAX_LEN],
+    len: Wire<F>,
+    cs: &mut ConstraintSystem<F>,
+) -> Wire<F> {
+    let one = cs.one();
+
+    // is_len[i] = (len == i)
+    let is_len = (0..=MAX_LEN)
+        .map(|i| {
+            let i = cs.alloc_const(F::from(i as u64));
+            len.is_equal(i)
+        })
+        .collect::<Vec<Wire<F>>>();
+
+    // len is at most MAX_LEN iff exactly one of the flags is set
+    let num_flags = cs.sum(&is_len.iter().map(|b| (*b, true)).collect::<Vec<_>>());
+    cs.assert_equal(num_flags, one, "len is greater than MAX_LEN");
+
+    // padded[i] = inputs[i] if i < len, 1 if i == len and 0 otherwise
+    let mut padded = Vec::with_capacity(MAX_LEN + 1);
+    let mut is_before_len = one;
+    for (i, is_len_i) in is_len.iter().enumerate() {
+        // 1 - sum(is_len[0..=i]) = (i < len)
+        is_before_len = is_before_len - *is_len_i;
+
+        if i < MAX_LEN {
+            padded.push(cs.deg_2_comb(
+                &[(is_before_len, F::ONE)],
+                &[(inputs[i], F::ONE)],
+                &[(*is_len_i, F::ONE)],
+            ));
+        } else {
+            padded.push(*is_len_i);
+        }
+    }
+
+    sponge_hash(VAR_LEN_DOMAIN_SEPARATOR, &padded, cs)
+}
+
+// Native counterpart of `poseidon_hash_var_len`, where the length is that of `inputs`
+pub fn poseidon_hash_var_len_native<F: FieldGC, const MAX_LEN: usize>(inputs: &[F]) -> F {
+    assert!(inputs.len() <= MAX_LEN, "the input is longer than MAX_LEN");
+
+    let mut padded = inputs.to_vec();
+    padded.push(F::ONE);
+    padded.resize(MAX_LEN + 1, F::ZERO);
+
+    sponge_hash_native(VAR_LEN_DOMAIN_SEPARATOR, &padded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::is_rejected;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    fn poseidon_hash_circuit<F: FieldGC, const N: usize>(cs: &mut ConstraintSystem<F>) {
+        let inputs: [Wire<F>; N] = cs.alloc_priv_inputs(N).try_into().unwrap();
+        let hash = poseidon_hash(&inputs, cs);
+
+        cs.expose_public(hash);
+    }
+
+    fn check_num_inputs<const N: usize>() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| poseidon_hash_circuit::<Fp, N>(cs);
+
+        let inputs: [Fp; N] = std::array::from_fn(|i| Fp::from(1234567 * (i as u64 + 1)));
+        let expected_hash = poseidon_hash_native(&inputs);
+
+        let mut cs = ConstraintSystem::new();
+        let pub_input = [expected_hash];
+        let witness = cs.gen_witness(synthesizer, &pub_input, &inputs);
+
+        cs.set_constraints(&synthesizer);
+        assert!(cs.is_sat(&witness, &pub_input), "{} inputs mismatch", N);
+
+        println!(
+            "Num constraints ({} inputs): {}",
+            N,
+            cs.num_constraints.unwrap()
+        );
+    }
+
+    #[test]
+    fn test_poseidon_hash() {
+        check_num_inputs::<0>();
+        check_num_inputs::<1>();
+        check_num_inputs::<2>();
+        check_num_inputs::<5>();
+
+        // Appending zeros changes the hash
+        let x = Fp::from(42u32);
+        assert_ne!(
+            poseidon_hash_native(&[x]),
+            poseidon_hash_native(&[x, Fp::from(0u32)])
+        );
+
+        // The hash of no inputs is permuted rather than squeezed from the initial state
+        assert_ne!(
+            poseidon_hash_native::<Fp, 0>(&[]),
+            tag::<Fp>(DOMAIN_SEPARATOR, 0)
+        );
+    }
+
+    const MAX_LEN: usize = 5;
+
+    fn poseidon_hash_var_len_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>) {
+        let inputs: [Wire<F>; MAX_LEN] = cs.alloc_priv_inputs(MAX_LEN).try_into().unwrap();
+        let len = cs.alloc_priv_input();
+
+        let hash = poseidon_hash_var_len(&inputs, len, cs);
+
+        cs.expose_public(hash);
+    }
+
+    #[test]
+    fn test_poseidon_hash_var_len() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| poseidon_hash_var_len_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+
+        let message = (0..MAX_LEN)
+            .map(|i| Fp::from(1234567 * (i as u64 + 1)))
+            .collect::<Vec<Fp>>();
+
+        for len in [0, 1, 3, MAX_LEN] {
+            let expected_hash = poseidon_hash_var_len_native::<Fp, MAX_LEN>(&message[..len]);
+
+            // The elements after the message are arbitrary
+            let mut priv_input = message[..len].to_vec();
+            priv_input.resize(MAX_LEN, Fp::from(7u32));
+            priv_input.push(Fp::from(len as u64));
+
+            let pub_input = [expected_hash];
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(cs.is_sat(&witness, &pub_input), "len {} mismatch", len);
+        }
+
+        // The padding makes messages that differ only in trailing zeros distinct
+        let zero = Fp::from(0u32);
+        assert_ne!(
+            poseidon_hash_var_len_native::<Fp, MAX_LEN>(&[zero]),
+            poseidon_hash_var_len_native::<Fp, MAX_LEN>(&[zero, zero])
+        );
+
+        // ...and the domain separator makes it distinct from the fixed-length hash
+        // of the padded message
+        let x = Fp::from(42u32);
+        let one = Fp::from(1u32);
+        assert_ne!(
+            poseidon_hash_var_len_native::<Fp, MAX_LEN>(&[x]),
+            poseidon_hash_native(&[x, one, zero, zero, zero, zero])
+        );
+    }
+
+    #[test]
+    fn test_poseidon_hash_var_len_rejects_long_len() {
+        let message = (0..MAX_LEN)
+            .map(|i| Fp::from(1234567 * (i as u64 + 1)))
+            .collect::<Vec<Fp>>();
+
+        // No length outside 0..=MAX_LEN is accepted, whatever the claimed hash
+        let claimed_hashes = [
+            poseidon_hash_var_len_native::<Fp, MAX_LEN>(&message),
+            poseidon_hash_var_len_native::<Fp, MAX_LEN>(&[]),
+        ];
+
+        for len in [MAX_LEN as u64 + 1, 1 << 40] {
+            let mut priv_input = message.clone();
+            priv_input.push(Fp::from(len));
+
+            for expected_hash in claimed_hashes {
+                assert!(
+                    is_rejected(
+                        poseidon_hash_var_len_circuit::<Fp>,
+                        &[expected_hash],
+                        &priv_input,
+                        &["len is greater than MAX_LEN"]
+                    ),
+                    "len {} accepted",
+                    len
+                );
+            }
+        }
+    }
+}
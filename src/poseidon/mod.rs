@@ -1,2 +1,4 @@
+pub mod hash;
 pub mod poseidon;
+pub mod poseidon2;
 pub mod sponge;
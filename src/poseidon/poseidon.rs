@@ -1,138 +1,535 @@
+use std::rc::Rc;
+
 use frontend::FieldGC;
 use frontend::{ConstraintSystem, Wire};
 use shockwave_plus::PoseidonConstants;
 
+// The sparse matrix [[m_00, row], [col, I]]
+#[derive(Clone)]
+struct SparseMatrix<F: FieldGC> {
+    m_00: F,
+    row: Vec<F>,
+    col: Vec<F>,
+}
+
+// Constants of the permutation with the partial rounds in the optimized form;
+// Appendix B of https://eprint.iacr.org/2019/458.pdf
+// - The round keys of the elements that skip the S-box in the partial rounds are moved
+//   through the linear layers into the first full round of the second half,
+//   so each partial round only adds a single round key.
+// - The MDS matrix of each partial round is factored as M = M'' * M',
+//   where M'' is sparse and M' = [[1, 0], [0, M_hat]] commutes with the partial S-box layer.
+//   The dense factors are moved backwards through the partial rounds
+//   and end up in the matrix of the last full round of the first half.
+// Deriving them inverts a matrix per partial round, so chips over the same constants
+// can share them through `PoseidonChip::new_with_optimized`.
+#[derive(Clone)]
+pub struct OptimizedConstants<F: FieldGC> {
+    // The round keys of each round; a single key per partial round
+    round_keys: Vec<Vec<F>>,
+    // The matrix of the last full round of the first half
+    pre_sparse_matrix: Vec<Vec<F>>,
+    sparse_matrices: Vec<SparseMatrix<F>>,
+}
+
+fn mat_vec_mul<F: FieldGC>(matrix: &[Vec<F>], v: &[F]) -> Vec<F> {
+    matrix
+        .iter()
+        .map(|row| row.iter().zip(v.iter()).map(|(a, b)| *a * b).sum())
+        .collect()
+}
+
+fn mat_mul<F: FieldGC>(a: &[Vec<F>], b: &[Vec<F>]) -> Vec<Vec<F>> {
+    a.iter()
+        .map(|row| {
+            (0..b[0].len())
+                .map(|j| {
+                    row.iter()
+                        .zip(b.iter())
+                        .map(|(a_ik, b_k)| *a_ik * b_k[j])
+                        .sum()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn transpose<F: FieldGC>(matrix: &[Vec<F>]) -> Vec<Vec<F>> {
+    (0..matrix[0].len())
+        .map(|j| matrix.iter().map(|row| row[j]).collect())
+        .collect()
+}
+
+// Gauss-Jordan elimination. The submatrices of an MDS matrix are invertible.
+fn mat_inverse<F: FieldGC>(matrix: &[Vec<F>]) -> Vec<Vec<F>> {
+    let n = matrix.len();
+    let mut a = matrix.to_vec();
+    let mut inv = (0..n)
+        .map(|i| (0..n).map(|j| F::from(i == j)).collect::<Vec<F>>())
+        .collect::<Vec<Vec<F>>>();
+
+    for c in 0..n {
+        let pivot = (c..n)
+            .find(|r| a[*r][c] != F::ZERO)
+            .expect("matrix is not invertible");
+        a.swap(c, pivot);
+        inv.swap(c, pivot);
+
+        let pivot_inv = a[c][c].inverse().unwrap();
+        for j in 0..n {
+            a[c][j] *= pivot_inv;
+            inv[c][j] *= pivot_inv;
+        }
+
+        for r in 0..n {
+            if r != c && a[r][c] != F::ZERO {
+                let factor = a[r][c];
+                for j in 0..n {
+                    let a_cj = a[c][j];
+                    let inv_cj = inv[c][j];
+                    a[r][j] -= factor * a_cj;
+                    inv[r][j] -= factor * inv_cj;
+                }
+            }
+        }
+    }
+
+    inv
+}
+
+impl<F: FieldGC> OptimizedConstants<F> {
+    pub fn new(constants: &PoseidonConstants<F>, width: usize) -> Self {
+        let mds = &constants.mds_matrix;
+        let half_full_rounds = constants.num_full_rounds / 2;
+        let num_partial_rounds = constants.num_partial_rounds;
+        let num_rounds = constants.num_full_rounds + num_partial_rounds;
+
+        let mut round_keys = (0..num_rounds)
+            .map(|r| constants.round_keys[r * width..(r + 1) * width].to_vec())
+            .collect::<Vec<Vec<F>>>();
+
+        // Move the round keys of the elements that skip the S-box forward
+        let mut carry = vec![F::ZERO; width];
+        for r in half_full_rounds..(half_full_rounds + num_partial_rounds) {
+            let mut keys = round_keys[r]
+                .iter()
+                .zip(carry.iter())
+                .map(|(k, c)| *k + c)
+                .collect::<Vec<F>>();
+
+            let key_0 = keys[0];
+            keys[0] = F::ZERO;
+            carry = mat_vec_mul(mds, &keys);
+
+            round_keys[r] = vec![key_0];
+        }
+        let first_full_round = half_full_rounds + num_partial_rounds;
+        for (k, c) in round_keys[first_full_round].iter_mut().zip(carry.iter()) {
+            *k += c;
+        }
+
+        // Factor the matrices of the partial rounds, starting from the last one
+        let mut sparse_matrices = Vec::with_capacity(num_partial_rounds);
+        let mut matrix = mds.clone();
+        for _ in 0..num_partial_rounds {
+            let m_hat = matrix[1..]
+                .iter()
+                .map(|row| row[1..].to_vec())
+                .collect::<Vec<Vec<F>>>();
+            let v = matrix[0][1..].to_vec();
+            let w = matrix[1..].iter().map(|row| row[0]).collect::<Vec<F>>();
+
+            // row = v * M_hat^-1
+            let row = mat_vec_mul(&transpose(&mat_inverse(&m_hat)), &v);
+            sparse_matrices.push(SparseMatrix {
+                m_00: matrix[0][0],
+                row,
+                col: w,
+            });
+
+            // The matrix of the previous round becomes M' * MDS
+            let mut m_prime = (0..width)
+                .map(|i| (0..width).map(|j| F::from(i == j)).collect::<Vec<F>>())
+                .collect::<Vec<Vec<F>>>();
+            for i in 1..width {
+                m_prime[i][1..].copy_from_slice(&m_hat[i - 1]);
+            }
+            matrix = mat_mul(&m_prime, mds);
+        }
+        sparse_matrices.reverse();
+
+        Self {
+            round_keys,
+            pre_sparse_matrix: matrix,
+            sparse_matrices,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct PoseidonChip<F: FieldGC, const WIDTH: usize> {
     pub state: [Wire<F>; WIDTH],
     pub pos: usize,
     constants: PoseidonConstants<F>,
-    cs: *mut ConstraintSystem<F>,
+    optimized: Rc<OptimizedConstants<F>>,
 }
 
 impl<F: FieldGC, const WIDTH: usize> PoseidonChip<F, WIDTH> {
-    pub fn new(cs_ptr: *mut ConstraintSystem<F>, constants: PoseidonConstants<F>) -> Self {
-        let cs = unsafe { &mut *cs_ptr };
+    pub fn new(cs: &mut ConstraintSystem<F>, constants: PoseidonConstants<F>) -> Self {
+        let optimized = Rc::new(OptimizedConstants::new(&constants, WIDTH));
+        Self::new_with_optimized(cs, constants, optimized)
+    }
+
+    // `optimized` must be derived from `constants`
+    pub fn new_with_optimized(
+        cs: &mut ConstraintSystem<F>,
+        constants: PoseidonConstants<F>,
+        optimized: Rc<OptimizedConstants<F>>,
+    ) -> Self {
         let zero = cs.alloc_const(F::ZERO);
         let init_state = [zero; WIDTH];
 
         Self {
             state: init_state,
             constants,
+            optimized,
             pos: 0,
-            cs: cs_ptr,
         }
     }
 
-    fn cs(&self) -> &mut ConstraintSystem<F> {
-        unsafe { &mut *self.cs as &mut ConstraintSystem<F> }
-    }
-
     pub fn reset(&mut self) {
-        let cs = self.cs();
-        let zero = cs.alloc_const(F::ZERO);
+        let zero = self.state[0].cs().alloc_const(F::ZERO);
         self.state = [zero; WIDTH];
         self.pos = 0;
     }
 
-    // MDS matrix multiplication
-    fn matrix_mul(&mut self) {
-        let mut result = [self.cs().one(); WIDTH];
+    // (x + round_key)^5
+    fn sbox(&self, x: Wire<F>, round_key: F, cs: &mut ConstraintSystem<F>) -> Wire<F> {
+        let one = cs.one();
+
+        let deg2_comb_a = [(x, F::ONE), (one, round_key)];
+        let deg2_comb_b = deg2_comb_a;
+
+        let square = cs.deg_2_comb(&deg2_comb_a, &deg2_comb_b, &[]);
+        let quadruple = square * square;
+
+        let deg2_comb_a_2 = [(quadruple, F::ONE)];
+
+        cs.deg_2_comb(&deg2_comb_a_2, &deg2_comb_b, &[])
+    }
+
+    // Dense matrix multiplication
+    fn mul_matrix(&self, matrix: &[Vec<F>], cs: &mut ConstraintSystem<F>) -> [Wire<F>; WIDTH] {
+        let one = cs.one();
+        let mut result = [one; WIDTH];
 
-        for (i, matrix) in self.constants.mds_matrix.iter().enumerate() {
-            let deg2_comb_a = [
-                (self.state[0], matrix[0]),
-                (self.state[1], matrix[1]),
-                (self.state[2], matrix[2]),
-            ];
+        for (i, matrix) in matrix.iter().enumerate() {
+            let deg2_comb_a = self
+                .state
+                .iter()
+                .zip(matrix.iter())
+                .map(|(s, m)| (*s, *m))
+                .collect::<Vec<(Wire<F>, F)>>();
 
-            let deg2_comb_b = [(self.cs().one(), F::ONE)];
+            let deg2_comb_b = [(one, F::ONE)];
             let deg_2_comb_c = [];
-            result[i] = self
-                .cs()
-                .deg_2_comb(&deg2_comb_a, &deg2_comb_b, &deg_2_comb_c);
+            result[i] = cs.deg_2_comb(&deg2_comb_a, &deg2_comb_b, &deg_2_comb_c);
         }
 
-        self.state = result;
+        result
+    }
+
+    // MDS matrix multiplication
+    #[cfg(test)]
+    fn matrix_mul(&mut self, cs: &mut ConstraintSystem<F>) {
+        self.state = self.mul_matrix(&self.constants.mds_matrix, cs);
     }
 
-    fn full_round(&mut self) {
+    #[cfg(test)]
+    fn full_round(&mut self, cs: &mut ConstraintSystem<F>) {
         let t = self.state.len();
 
         // Add round constants and apply the S-boxes
         for i in 0..t {
-            let deg2_comb_a = [
-                (self.state[i], F::ONE),
-                (self.cs().one(), self.constants.round_keys[self.pos + i]),
-            ];
-
-            let deg2_comb_b = deg2_comb_a;
-
-            let square = self.cs().deg_2_comb(&deg2_comb_a, &deg2_comb_b, &[]);
-            let quadruple = square * square;
+            self.state[i] = self.sbox(self.state[i], self.constants.round_keys[self.pos + i], cs);
+        }
 
-            let deg2_comb_a_2 = [(quadruple, F::ONE)];
+        self.matrix_mul(cs);
 
-            let deg2_comb_b = deg2_comb_a;
+        // Update the position of the round constants that are added
+        self.pos += self.state.len();
+    }
 
-            self.state[i] = self.cs().deg_2_comb(&deg2_comb_a_2, &deg2_comb_b, &[]);
+    #[cfg(test)]
+    fn partial_round(&mut self, cs: &mut ConstraintSystem<F>) {
+        // Apply the round constants to the elements that skip the S-box
+        for i in 1..WIDTH {
+            self.state[i] = cs.add_const(self.state[i], self.constants.round_keys[self.pos + i]);
         }
 
-        self.matrix_mul();
+        // S-box
+        self.state[0] = self.sbox(self.state[0], self.constants.round_keys[self.pos], cs);
+
+        self.matrix_mul(cs);
 
         // Update the position of the round constants that are added
         self.pos += self.state.len();
     }
 
-    fn partial_round(&mut self) {
-        // Apply the round constants
-        for i in 1..3 {
-            self.state[i] = self
-                .cs()
-                .add_const(self.state[i], self.constants.round_keys[self.pos + i]);
+    // A full round with the optimized round keys of round `r` and the given matrix
+    fn optimized_full_round(&mut self, r: usize, matrix: &[Vec<F>], cs: &mut ConstraintSystem<F>) {
+        for i in 0..WIDTH {
+            self.state[i] = self.sbox(self.state[i], self.optimized.round_keys[r][i], cs);
         }
 
-        // S-box
-        let deg2_comb_a = [
-            (self.state[0], F::ONE),
-            (self.cs().one(), self.constants.round_keys[self.pos + 0]),
-        ];
-
-        let deg2_comb_b = deg2_comb_a;
+        self.state = self.mul_matrix(matrix, cs);
+    }
 
-        let square = self.cs().deg_2_comb(&deg2_comb_a, &deg2_comb_b, &[]);
-        let quadruple = square * square;
+    // A partial round with a single round key and the sparse matrix,
+    // which costs O(WIDTH) linear terms instead of O(WIDTH^2)
+    fn sparse_partial_round(&mut self, r: usize, i: usize, cs: &mut ConstraintSystem<F>) {
+        let one = cs.one();
+        let matrix = &self.optimized.sparse_matrices[i];
 
-        let deg2_comb_a_2 = [(quadruple, F::ONE)];
+        let x_0 = self.sbox(self.state[0], self.optimized.round_keys[r][0], cs);
 
-        let deg2_comb_b = deg2_comb_a;
+        let mut result = [one; WIDTH];
 
-        self.state[0] = self.cs().deg_2_comb(&deg2_comb_a_2, &deg2_comb_b, &[]);
+        // m_00 * x_0 + row * state[1..]
+        let mut deg2_comb_a = vec![(x_0, matrix.m_00)];
+        deg2_comb_a.extend(
+            self.state[1..]
+                .iter()
+                .zip(matrix.row.iter())
+                .map(|(s, m)| (*s, *m)),
+        );
+        result[0] = cs.deg_2_comb(&deg2_comb_a, &[(one, F::ONE)], &[]);
 
-        self.matrix_mul();
+        // col * x_0 + state[1..]
+        for j in 1..WIDTH {
+            result[j] = cs.deg_2_comb(
+                &[(x_0, matrix.col[j - 1]), (self.state[j], F::ONE)],
+                &[(one, F::ONE)],
+                &[],
+            );
+        }
 
-        // Update the position of the round constants that are added
-        self.pos += self.state.len();
+        self.state = result;
     }
 
+    // The permutation with the optimized partial rounds.
+    // The output is the same as `permute_naive`, with fewer linear terms.
+    // The constraints are added to the constraint system of the state.
     pub fn permute(&mut self) {
-        // ########################
-        // First half of the full rounds
-        // ########################
+        // Copy the wire so that `cs` doesn't borrow the state
+        let wire = self.state[0];
+        let cs = wire.cs();
+
+        let half_full_rounds = self.constants.num_full_rounds / 2;
+        let num_partial_rounds = self.constants.num_partial_rounds;
+        let mds = self.constants.mds_matrix.clone();
+        let pre_sparse_matrix = self.optimized.pre_sparse_matrix.clone();
+
+        // First half of full rounds
+        for r in 0..half_full_rounds {
+            if r == half_full_rounds - 1 {
+                self.optimized_full_round(r, &pre_sparse_matrix, cs);
+            } else {
+                self.optimized_full_round(r, &mds, cs);
+            }
+        }
+
+        // Partial rounds
+        for i in 0..num_partial_rounds {
+            self.sparse_partial_round(half_full_rounds + i, i, cs);
+        }
+
+        // Second half of full rounds
+        for r in
+            (half_full_rounds + num_partial_rounds)..(2 * half_full_rounds + num_partial_rounds)
+        {
+            self.optimized_full_round(r, &mds, cs);
+        }
+    }
+
+    // The permutation as specified, with a dense MDS matrix multiplication in every round.
+    // Only used to check `permute` against.
+    #[cfg(test)]
+    fn permute_naive(&mut self) {
+        let wire = self.state[0];
+        let cs = wire.cs();
 
         // First half of full rounds
         for _ in 0..self.constants.num_full_rounds / 2 {
-            self.full_round();
+            self.full_round(cs);
         }
 
         // Partial rounds
         for _ in 0..self.constants.num_partial_rounds {
-            self.partial_round();
+            self.partial_round(cs);
         }
 
         // Second half of full rounds
         for _ in 0..self.constants.num_full_rounds / 2 {
-            self.full_round();
+            self.full_round(cs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{poseidon_sponge_native, PoseidonSpongeChip};
+    use shockwave_plus::{IOPattern, PoseidonCurve, SpongeOp};
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    const WIDTH: usize = 3;
+
+    // The input fills the whole rate of WIDTH - 1 elements,
+    // so every lane the permutation outputs to is checked
+    fn io_pattern<const WIDTH: usize>() -> IOPattern {
+        IOPattern::new(vec![
+            SpongeOp::Absorb(WIDTH - 1),
+            SpongeOp::Squeeze(WIDTH - 1),
+        ])
+    }
+
+    fn poseidon_circuit<F: FieldGC, const WIDTH: usize>(cs: &mut ConstraintSystem<F>) {
+        let input = cs.alloc_priv_inputs(WIDTH - 1);
+
+        let mut poseidon_sponge = PoseidonSpongeChip::<F, WIDTH>::new(
+            b"test",
+            io_pattern::<WIDTH>(),
+            PoseidonCurve::SECP256K1,
+            cs,
+        );
+        poseidon_sponge.absorb(&input);
+
+        for y in poseidon_sponge.squeeze(WIDTH - 1) {
+            cs.expose_public(y);
+        }
+    }
+
+    fn check_width<const WIDTH: usize>() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| poseidon_circuit::<Fp, WIDTH>(cs);
+
+        let input = (0..(WIDTH - 1))
+            .map(|i| Fp::from(1234567 * (i as u64 + 1)))
+            .collect::<Vec<Fp>>();
+
+        // The chip's default capacity is 1
+        let mut poseidon = poseidon_sponge_native::<Fp, WIDTH>(
+            b"test",
+            io_pattern::<WIDTH>(),
+            PoseidonCurve::SECP256K1,
+            1,
+        );
+        poseidon.absorb(&input);
+        let expected = poseidon.squeeze(WIDTH - 1);
+
+        let mut cs = ConstraintSystem::new();
+        let witness = cs.gen_witness(synthesizer, &expected, &input);
+
+        cs.set_constraints(&synthesizer);
+        assert!(cs.is_sat(&witness, &expected), "width {} mismatch", WIDTH);
+
+        println!(
+            "Num constraints (width {}): {}",
+            WIDTH,
+            cs.num_constraints.unwrap()
+        );
+    }
+
+    #[test]
+    fn test_poseidon_widths() {
+        check_width::<2>();
+        check_width::<3>();
+        check_width::<5>();
+        check_width::<9>();
+        check_width::<17>();
+    }
+
+    fn permute<F: FieldGC>(
+        input: &[Wire<F>],
+        naive: bool,
+        optimized: &Rc<OptimizedConstants<F>>,
+        cs: &mut ConstraintSystem<F>,
+    ) -> [Wire<F>; WIDTH] {
+        let constants = PoseidonConstants::<F>::new(PoseidonCurve::SECP256K1, WIDTH);
+        let mut poseidon =
+            PoseidonChip::<F, WIDTH>::new_with_optimized(cs, constants, optimized.clone());
+        poseidon.state = input.try_into().unwrap();
+
+        if naive {
+            poseidon.permute_naive();
+        } else {
+            poseidon.permute();
+        }
+
+        poseidon.state
+    }
+
+    // Derived once and shared by the naive and the optimized chips
+    fn optimized_constants<F: FieldGC>() -> Rc<OptimizedConstants<F>> {
+        let constants = PoseidonConstants::<F>::new(PoseidonCurve::SECP256K1, WIDTH);
+        Rc::new(OptimizedConstants::new(&constants, WIDTH))
+    }
+
+    fn optimized_partial_rounds_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>) {
+        let input = cs.alloc_priv_inputs(WIDTH);
+        let optimized = optimized_constants();
+
+        let expected = permute(&input, true, &optimized, cs);
+        let result = permute(&input, false, &optimized, cs);
+
+        for (x, y) in expected.iter().zip(result.iter()) {
+            cs.assert_equal(*x, *y, "optimized permutation mismatch");
+        }
+    }
+
+    #[test]
+    fn test_optimized_partial_rounds() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| optimized_partial_rounds_circuit(cs);
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        for input in [
+            [Fp::from(0), Fp::from(0), Fp::from(0)],
+            [Fp::from(1), Fp::from(2), Fp::from(3)],
+        ] {
+            let witness = cs.gen_witness(synthesizer, &[], &input);
+            assert!(cs.is_sat(&witness, &[]));
         }
+
+        // Compare the cost of the two permutations
+        let naive_synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let input = cs.alloc_priv_inputs(WIDTH);
+            permute(&input, true, &optimized_constants(), cs);
+        };
+        let optimized_synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let input = cs.alloc_priv_inputs(WIDTH);
+            permute(&input, false, &optimized_constants(), cs);
+        };
+
+        let mut naive_cs = ConstraintSystem::new();
+        naive_cs.set_constraints(&naive_synthesizer);
+        let mut optimized_cs = ConstraintSystem::new();
+        optimized_cs.set_constraints(&optimized_synthesizer);
+
+        println!(
+            "Num constraints (naive): {}",
+            naive_cs.num_constraints.unwrap()
+        );
+        println!(
+            "Num constraints (optimized): {}",
+            optimized_cs.num_constraints.unwrap()
+        );
+
+        // The round keys of the elements that skip the S-box are folded away,
+        // so the partial rounds no longer add them with a constraint each
+        assert!(optimized_cs.num_constraints.unwrap() < naive_cs.num_constraints.unwrap());
+        assert!(optimized_cs.num_vars() < naive_cs.num_vars());
     }
 }
@@ -0,0 +1,408 @@
+use std::collections::VecDeque;
+
+use frontend::ark_ff::PrimeField;
+use frontend::FieldGC;
+use frontend::{ConstraintSystem, Wire};
+use num_bigint::BigUint;
+
+// Poseidon2 with the x^5 S-box; https://eprint.iacr.org/2023/323.pdf
+// We follow the reference implementation https://github.com/HorizenLabs/poseidon2.
+// Only widths 2 and 3 are supported, for which the linear layers are
+// M_E = circ(2, 1, ..., 1) and M_I = J + diag(1, ..., 1, 2).
+// The round numbers are those of the reference instances for ~256-bit fields.
+
+const NUM_FULL_ROUNDS: usize = 8;
+const NUM_PARTIAL_ROUNDS: usize = 56;
+
+// The Grain LFSR used to generate the round constants of Poseidon and Poseidon2
+struct Grain {
+    bits: VecDeque<bool>,
+}
+
+impl Grain {
+    fn new(field_bits: usize, width: usize) -> Self {
+        let mut bits = VecDeque::with_capacity(80);
+        let mut push = |value: usize, num_bits: usize| {
+            for i in (0..num_bits).rev() {
+                bits.push_back(value >> i & 1 == 1);
+            }
+        };
+
+        push(1, 2); // Prime field
+        push(0, 4); // x^alpha S-box
+        push(field_bits, 12);
+        push(width, 12);
+        push(NUM_FULL_ROUNDS, 10);
+        push(NUM_PARTIAL_ROUNDS, 10);
+        push((1 << 30) - 1, 30);
+
+        let mut grain = Self { bits };
+        for _ in 0..160 {
+            grain.step();
+        }
+
+        grain
+    }
+
+    fn step(&mut self) -> bool {
+        let b = &self.bits;
+        let new_bit = b[62] ^ b[51] ^ b[38] ^ b[23] ^ b[13] ^ b[0];
+        self.bits.pop_front();
+        self.bits.push_back(new_bit);
+        new_bit
+    }
+
+    // Self-shrinking: a bit is output only if the preceding bit is one
+    fn next_bit(&mut self) -> bool {
+        loop {
+            if self.step() {
+                return self.step();
+            }
+            self.step();
+        }
+    }
+
+    // Rejection sampling of field elements, MSB first
+    fn next_field_element<F: PrimeField>(&mut self) -> F {
+        let modulus: BigUint = F::MODULUS.into();
+        loop {
+            let mut x = BigUint::from(0u32);
+            for _ in 0..F::MODULUS_BIT_SIZE {
+                x = (x << 1) + BigUint::from(self.next_bit() as u32);
+            }
+
+            if x < modulus {
+                return F::from(x);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Poseidon2Constants<F: PrimeField> {
+    // WIDTH keys for each full round and a single key for each partial round
+    pub round_keys: Vec<Vec<F>>,
+    pub num_full_rounds: usize,
+    pub num_partial_rounds: usize,
+}
+
+impl<F: PrimeField> Poseidon2Constants<F> {
+    pub fn new(width: usize) -> Self {
+        assert!(
+            width == 2 || width == 3,
+            "Poseidon2 is only implemented for widths 2 and 3"
+        );
+
+        let mut grain = Grain::new(F::MODULUS_BIT_SIZE as usize, width);
+
+        let half_full_rounds = NUM_FULL_ROUNDS / 2;
+        let round_keys = (0..NUM_FULL_ROUNDS + NUM_PARTIAL_ROUNDS)
+            .map(|r| {
+                let is_partial = r >= half_full_rounds && r < half_full_rounds + NUM_PARTIAL_ROUNDS;
+                let num_keys = if is_partial { 1 } else { width };
+                (0..num_keys).map(|_| grain.next_field_element()).collect()
+            })
+            .collect();
+
+        Self {
+            round_keys,
+            num_full_rounds: NUM_FULL_ROUNDS,
+            num_partial_rounds: NUM_PARTIAL_ROUNDS,
+        }
+    }
+
+    // The diagonal of M_I - J
+    fn internal_diag(width: usize) -> Vec<F> {
+        let mut diag = vec![F::ONE; width];
+        diag[width - 1] = F::from(2u32);
+        diag
+    }
+}
+
+// Native reference implementation of the permutation
+#[derive(Clone)]
+pub struct Poseidon2<F: PrimeField, const WIDTH: usize> {
+    pub state: [F; WIDTH],
+    constants: Poseidon2Constants<F>,
+}
+
+impl<F: PrimeField, const WIDTH: usize> Poseidon2<F, WIDTH> {
+    pub fn new() -> Self {
+        Self {
+            state: [F::ZERO; WIDTH],
+            constants: Poseidon2Constants::new(WIDTH),
+        }
+    }
+
+    fn sbox(x: F) -> F {
+        let square = x * x;
+        square * square * x
+    }
+
+    fn external_matrix_mul(&mut self) {
+        let sum: F = self.state.iter().sum();
+        for x in self.state.iter_mut() {
+            *x += sum;
+        }
+    }
+
+    fn internal_matrix_mul(&mut self) {
+        let sum: F = self.state.iter().sum();
+        let diag = Poseidon2Constants::<F>::internal_diag(WIDTH);
+        for (x, d) in self.state.iter_mut().zip(diag.iter()) {
+            *x = *x * d + sum;
+        }
+    }
+
+    fn full_round(&mut self, r: usize) {
+        for (x, k) in self
+            .state
+            .iter_mut()
+            .zip(self.constants.round_keys[r].iter())
+        {
+            *x = Self::sbox(*x + k);
+        }
+        self.external_matrix_mul();
+    }
+
+    pub fn permute(&mut self) {
+        let half_full_rounds = self.constants.num_full_rounds / 2;
+        let num_partial_rounds = self.constants.num_partial_rounds;
+
+        self.external_matrix_mul();
+
+        for r in 0..half_full_rounds {
+            self.full_round(r);
+        }
+
+        for r in half_full_rounds..(half_full_rounds + num_partial_rounds) {
+            self.state[0] = Self::sbox(self.state[0] + self.constants.round_keys[r][0]);
+            self.internal_matrix_mul();
+        }
+
+        for r in (half_full_rounds + num_partial_rounds)..self.constants.round_keys.len() {
+            self.full_round(r);
+        }
+    }
+}
+
+impl<F: PrimeField, const WIDTH: usize> Default for Poseidon2<F, WIDTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone)]
+pub struct Poseidon2Chip<F: FieldGC, const WIDTH: usize> {
+    pub state: [Wire<F>; WIDTH],
+    constants: Poseidon2Constants<F>,
+}
+
+impl<F: FieldGC, const WIDTH: usize> Poseidon2Chip<F, WIDTH> {
+    pub fn new(cs: &mut ConstraintSystem<F>) -> Self {
+        let zero = cs.alloc_const(F::ZERO);
+
+        Self {
+            state: [zero; WIDTH],
+            constants: Poseidon2Constants::new(WIDTH),
+        }
+    }
+
+    // (x + round_key)^5
+    fn sbox(x: Wire<F>, round_key: F, cs: &mut ConstraintSystem<F>) -> Wire<F> {
+        let one = cs.one();
+
+        let deg2_comb_a = [(x, F::ONE), (one, round_key)];
+        let square = cs.deg_2_comb(&deg2_comb_a, &deg2_comb_a, &[]);
+        let quadruple = square * square;
+
+        cs.deg_2_comb(&[(quadruple, F::ONE)], &deg2_comb_a, &[])
+    }
+
+    // Multiplies the state by J + diag, one linear combination per element
+    fn matrix_mul(&mut self, diag: &[F], cs: &mut ConstraintSystem<F>) {
+        let one = cs.one();
+        let mut result = self.state;
+
+        for i in 0..WIDTH {
+            let deg2_comb_a = self
+                .state
+                .iter()
+                .enumerate()
+                .map(|(j, x)| (*x, if i == j { F::ONE + diag[i] } else { F::ONE }))
+                .collect::<Vec<(Wire<F>, F)>>();
+
+            result[i] = cs.deg_2_comb(&deg2_comb_a, &[(one, F::ONE)], &[]);
+        }
+
+        self.state = result;
+    }
+
+    fn external_matrix_mul(&mut self, cs: &mut ConstraintSystem<F>) {
+        self.matrix_mul(&[F::ONE; WIDTH], cs);
+    }
+
+    fn internal_matrix_mul(&mut self, cs: &mut ConstraintSystem<F>) {
+        self.matrix_mul(&Poseidon2Constants::<F>::internal_diag(WIDTH), cs);
+    }
+
+    fn full_round(&mut self, r: usize, cs: &mut ConstraintSystem<F>) {
+        for (x, k) in self
+            .state
+            .iter_mut()
+            .zip(self.constants.round_keys[r].iter())
+        {
+            *x = Self::sbox(*x, *k, cs);
+        }
+        self.external_matrix_mul(cs);
+    }
+
+    // The constraints are added to the constraint system of the state
+    pub fn permute(&mut self) {
+        // Copy the wire so that `cs` doesn't borrow the state
+        let wire = self.state[0];
+        let cs = wire.cs();
+
+        let half_full_rounds = self.constants.num_full_rounds / 2;
+        let num_partial_rounds = self.constants.num_partial_rounds;
+
+        self.external_matrix_mul(cs);
+
+        for r in 0..half_full_rounds {
+            self.full_round(r, cs);
+        }
+
+        for r in half_full_rounds..(half_full_rounds + num_partial_rounds) {
+            self.state[0] = Self::sbox(self.state[0], self.constants.round_keys[r][0], cs);
+            self.internal_matrix_mul(cs);
+        }
+
+        for r in (half_full_rounds + num_partial_rounds)..self.constants.round_keys.len() {
+            self.full_round(r, cs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PoseidonChip;
+    use shockwave_plus::{PoseidonConstants, PoseidonCurve};
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    fn from_hex<F: PrimeField>(hex: &str) -> F {
+        F::from(BigUint::parse_bytes(hex.as_bytes(), 16).unwrap())
+    }
+
+    // Test vector of the reference implementation for BN254 (poseidon2_instance_bn256)
+    #[test]
+    fn test_poseidon2_native_bn254() {
+        type Fr = ark_bn254::Fr;
+
+        let mut poseidon2 = Poseidon2::<Fr, 3>::new();
+        poseidon2.state = [Fr::from(0u32), Fr::from(1u32), Fr::from(2u32)];
+        poseidon2.permute();
+
+        let expected = [
+            "0bb61d24daca55eebcb1929a82650f328134334da98ea4f847f760054f4a3033",
+            "303b6f7c86d043bfcbcc80214f26a30277a15d3f74ca654992defe7ff8d03570",
+            "1ed25194542b12eef8617361c3ba7c52e660b145994427cc86296242cf766ec8",
+        ]
+        .map(from_hex::<Fr>);
+
+        assert_eq!(poseidon2.state, expected);
+    }
+
+    // There are no published test vectors for the secp256k1 base field.
+    // These are computed by scripts/poseidon2_vectors.py, a standalone implementation of
+    // the reference constant generation and permutation, which first checks itself
+    // against the BN254 vector above.
+    #[test]
+    fn test_poseidon2_native_secp256k1() {
+        let mut poseidon2 = Poseidon2::<Fp, 3>::new();
+        poseidon2.state = [Fp::from(0u32), Fp::from(1u32), Fp::from(2u32)];
+        poseidon2.permute();
+
+        let expected = [
+            "7243884bf99f1c584874ca3bfe16709d65ea881638f85586f77d8f22c6fb39b4",
+            "21179c0588867fff471ae8ab2cdfa99e61d13a36c2dfb726fd50f536bfda95c1",
+            "31f23e3a3a6da0a50e37b4776ffb0c779dda9ff1078baba277ef73ff76e0ebb0",
+        ]
+        .map(from_hex::<Fp>);
+        assert_eq!(poseidon2.state, expected);
+
+        let mut poseidon2 = Poseidon2::<Fp, 2>::new();
+        poseidon2.state = [Fp::from(0u32), Fp::from(1u32)];
+        poseidon2.permute();
+
+        let expected = [
+            "38dc875f5a0e96245ed2e0f9e5d210427f036db3a6acd77c230d1fea1d052753",
+            "de0e150518f7f924f9693e3aa435decad9facd7f96f0316f694a9b366e95e45e",
+        ]
+        .map(from_hex::<Fp>);
+        assert_eq!(poseidon2.state, expected);
+    }
+
+    fn poseidon2_circuit<F: FieldGC, const WIDTH: usize>(cs: &mut ConstraintSystem<F>) {
+        let input = cs.alloc_priv_inputs(WIDTH);
+
+        let mut poseidon2 = Poseidon2Chip::<F, WIDTH>::new(cs);
+        poseidon2.state = input.try_into().unwrap();
+        poseidon2.permute();
+
+        for x in poseidon2.state {
+            cs.expose_public(x);
+        }
+    }
+
+    fn check_width<const WIDTH: usize>() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| poseidon2_circuit::<Fp, WIDTH>(cs);
+
+        let input = (0..WIDTH)
+            .map(|i| Fp::from(1234567 * (i as u64 + 1)))
+            .collect::<Vec<Fp>>();
+
+        let mut poseidon2 = Poseidon2::<Fp, WIDTH>::new();
+        poseidon2.state = input.clone().try_into().unwrap();
+        poseidon2.permute();
+
+        let mut cs = ConstraintSystem::new();
+        let pub_input = poseidon2.state;
+        let witness = cs.gen_witness(synthesizer, &pub_input, &input);
+
+        cs.set_constraints(&synthesizer);
+        assert!(cs.is_sat(&witness, &pub_input));
+    }
+
+    #[test]
+    fn test_poseidon2() {
+        check_width::<2>();
+        check_width::<3>();
+
+        // Compare the cost with the Poseidon permutation
+        let poseidon2_synthesizer = |cs: &mut ConstraintSystem<Fp>| poseidon2_circuit::<Fp, 3>(cs);
+        let poseidon_synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let input = cs.alloc_priv_inputs(3);
+
+            let constants = PoseidonConstants::<Fp>::new(PoseidonCurve::SECP256K1, 3);
+            let mut poseidon = PoseidonChip::<Fp, 3>::new(cs, constants);
+            poseidon.state = input.try_into().unwrap();
+            poseidon.permute();
+        };
+
+        let mut poseidon2_cs = ConstraintSystem::new();
+        poseidon2_cs.set_constraints(&poseidon2_synthesizer);
+        let mut poseidon_cs = ConstraintSystem::new();
+        poseidon_cs.set_constraints(&poseidon_synthesizer);
+
+        println!(
+            "Num constraints (Poseidon2): {}",
+            poseidon2_cs.num_constraints.unwrap()
+        );
+        println!(
+            "Num constraints (Poseidon): {}",
+            poseidon_cs.num_constraints.unwrap()
+        );
+    }
+}
@@ -1,20 +1,76 @@
 use std::result::Result;
 
 use frontend::{ConstraintSystem, Wire};
-use shockwave_plus::{FieldGC, IOPattern, PoseidonConstants, PoseidonCurve, PoseidonSponge};
+use shockwave_plus::{
+    FieldGC, IOPattern, PoseidonConstants, PoseidonCurve, PoseidonSponge, SpongeOp,
+};
 
 use crate::PoseidonChip;
 
+// Consecutive operations of the same kind are merged and empty operations are dropped,
+// so that e.g. [Absorb(1), Absorb(1), Squeeze(1)] is the same pattern as [Absorb(2), Squeeze(1)].
+fn aggregate_io_pattern(io_pattern: &IOPattern) -> Vec<SpongeOp> {
+    let mut ops: Vec<SpongeOp> = vec![];
+    for op in &io_pattern.0 {
+        match (ops.last_mut(), op) {
+            (_, SpongeOp::Absorb(0)) | (_, SpongeOp::Squeeze(0)) => {}
+            (Some(SpongeOp::Absorb(n)), SpongeOp::Absorb(m)) => *n += m,
+            (Some(SpongeOp::Squeeze(n)), SpongeOp::Squeeze(m)) => *n += m,
+            (_, SpongeOp::Absorb(m)) => ops.push(SpongeOp::Absorb(*m)),
+            (_, SpongeOp::Squeeze(m)) => ops.push(SpongeOp::Squeeze(*m)),
+        }
+    }
+
+    ops
+}
+
 // Implements SAFE (Sponge API for Field Elements): https://hackmd.io/bHgsH6mMStCVibM_wYvb2w
+// Every absorb and squeeze is checked against the IO pattern. The first call that deviates
+// from it panics right away, or is only reported by `finish` if `strict` is unset.
+// The check uses the aggregated pattern, so a call may consume
+// only a part of an operation, e.g. absorb(1) twice matches Absorb(2).
 #[derive(Clone)]
 pub struct PoseidonSpongeChip<F: FieldGC, const WIDTH: usize> {
     pub absorb_pos: usize,
     pub squeeze_pos: usize,
+    // The number of operations of the aggregated IO pattern that have been completed
     pub io_count: usize,
     pub io_pattern: IOPattern,
+    // The aggregated IO pattern, with the lengths of the operations still to be performed
+    io_ops: Vec<SpongeOp>,
     pub rate: usize,
     pub capacity: usize,
     pub poseidon: PoseidonChip<F, WIDTH>,
+    // Panic on the first call that deviates from the IO pattern. Set by default.
+    pub strict: bool,
+    // The first deviation from the IO pattern
+    error: Option<String>,
+}
+
+// The default capacity, which gives the sponge a rate of WIDTH - 1
+const DEFAULT_CAPACITY: usize = 1;
+
+fn rate(width: usize, capacity: usize) -> usize {
+    assert!(
+        capacity > 0 && capacity < width,
+        "the capacity must be between 1 and WIDTH - 1"
+    );
+
+    width - capacity
+}
+
+// Native counterpart of `PoseidonSpongeChip::new_with_capacity`
+pub fn poseidon_sponge_native<F: FieldGC, const WIDTH: usize>(
+    domain_separator: &[u8],
+    io_pattern: IOPattern,
+    curve: PoseidonCurve,
+    capacity: usize,
+) -> PoseidonSponge<F, WIDTH> {
+    let mut sponge = PoseidonSponge::<F, WIDTH>::new(domain_separator, curve, io_pattern);
+    sponge.rate = rate(WIDTH, capacity);
+    sponge.capacity = capacity;
+
+    sponge
 }
 
 impl<F: FieldGC, const WIDTH: usize> PoseidonSpongeChip<F, WIDTH> {
@@ -22,9 +78,21 @@ impl<F: FieldGC, const WIDTH: usize> PoseidonSpongeChip<F, WIDTH> {
         domain_separator: &[u8],
         io_pattern: IOPattern,
         curve: PoseidonCurve,
-        cs_ptr: *mut ConstraintSystem<F>,
+        cs: &mut ConstraintSystem<F>,
+    ) -> Self {
+        Self::new_with_capacity(domain_separator, io_pattern, curve, DEFAULT_CAPACITY, cs)
+    }
+
+    // The rate is WIDTH - capacity. The input is absorbed into the first `rate` elements
+    // of the state and the output is squeezed from them, as in the native sponge.
+    pub fn new_with_capacity(
+        domain_separator: &[u8],
+        io_pattern: IOPattern,
+        curve: PoseidonCurve,
+        capacity: usize,
+        cs: &mut ConstraintSystem<F>,
     ) -> Self {
-        let cs = unsafe { &mut *cs_ptr };
+        let rate = rate(WIDTH, capacity);
 
         let constants = PoseidonConstants::<F>::new(curve, WIDTH);
 
@@ -41,14 +109,19 @@ impl<F: FieldGC, const WIDTH: usize> PoseidonSpongeChip<F, WIDTH> {
         let mut poseidon = poseidon;
         poseidon.state = state;
 
+        let io_ops = aggregate_io_pattern(&io_pattern);
+
         Self {
             absorb_pos: 0,
             squeeze_pos: 0,
             io_count: 0,
             io_pattern,
-            rate: 2,
-            capacity: 1,
+            io_ops,
+            rate,
+            capacity,
             poseidon,
+            strict: true,
+            error: None,
         }
     }
 
@@ -57,6 +130,8 @@ impl<F: FieldGC, const WIDTH: usize> PoseidonSpongeChip<F, WIDTH> {
             return;
         }
 
+        self.check_io(SpongeOp::Absorb(x.len()));
+
         for x_i in x {
             if self.absorb_pos == self.rate {
                 self.permute();
@@ -67,9 +142,6 @@ impl<F: FieldGC, const WIDTH: usize> PoseidonSpongeChip<F, WIDTH> {
             self.absorb_pos += 1;
         }
 
-        // assert_eq!(self.io_pattern.0[self.io_count], SpongeOp::Absorb(x.len()));
-
-        self.io_count += 1;
         self.squeeze_pos = self.rate;
     }
 
@@ -79,6 +151,8 @@ impl<F: FieldGC, const WIDTH: usize> PoseidonSpongeChip<F, WIDTH> {
             return vec![];
         }
 
+        self.check_io(SpongeOp::Squeeze(length));
+
         for _ in 0..length {
             if self.squeeze_pos == self.rate {
                 self.permute();
@@ -90,13 +164,61 @@ impl<F: FieldGC, const WIDTH: usize> PoseidonSpongeChip<F, WIDTH> {
             self.squeeze_pos += 1;
         }
 
-        self.io_count += 1;
         y
     }
 
+    // Returns the first deviation from the IO pattern, if any,
+    // or an error if the pattern isn't complete
     pub fn finish(&self) -> Result<(), String> {
-        if self.io_count != self.io_pattern.0.len() {
-            return Err("IO pattern mismatch".to_string());
+        if let Some(error) = &self.error {
+            return Err(error.clone());
+        }
+
+        if self.io_count != self.io_ops.len() {
+            return Err(format!(
+                "IO pattern mismatch: the sponge was finished before {:?}",
+                self.io_ops[self.io_count]
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn check_io(&mut self, op: SpongeOp) {
+        if let Err(error) = self.match_io(op) {
+            if self.strict {
+                panic!("{}", error);
+            }
+
+            self.error.get_or_insert(error);
+        }
+    }
+
+    // Consumes `op` from the aggregated IO pattern
+    fn match_io(&mut self, op: SpongeOp) -> Result<(), String> {
+        let expected = self.io_ops.get_mut(self.io_count);
+        let remaining = match (expected, &op) {
+            (Some(SpongeOp::Absorb(n)), SpongeOp::Absorb(len))
+            | (Some(SpongeOp::Squeeze(n)), SpongeOp::Squeeze(len))
+                if *len <= *n =>
+            {
+                *n -= len;
+                *n
+            }
+            (expected, _) => {
+                let expected = match expected {
+                    Some(expected) => format!("{:?}", expected),
+                    None => "the end of the pattern".to_string(),
+                };
+                return Err(format!(
+                    "IO pattern mismatch at operation {}: expected {}, got {:?}",
+                    self.io_count, expected, op
+                ));
+            }
+        };
+
+        if remaining == 0 {
+            self.io_count += 1;
         }
 
         Ok(())
@@ -118,12 +240,16 @@ mod tests {
     const WIDTH: usize = 3;
     const RATE: usize = 2;
 
+    fn io_pattern() -> IOPattern {
+        IOPattern::new(vec![SpongeOp::Absorb(RATE), SpongeOp::Squeeze(1)])
+    }
+
     fn poseidon_sponge_circuit<F: FieldGC>(cs: &mut ConstraintSystem<F>) {
         let input = cs.alloc_priv_inputs(RATE);
 
         let mut poseidon_sponge = PoseidonSpongeChip::<F, WIDTH>::new(
             b"test",
-            IOPattern::new(vec![]),
+            io_pattern(),
             PoseidonCurve::SECP256K1,
             cs,
         );
@@ -142,11 +268,8 @@ mod tests {
         let input = [Fp::from(1234567), Fp::from(109987)];
 
         // Compute the expected hash
-        let mut poseidon = PoseidonSponge::<Fp, WIDTH>::new(
-            b"test",
-            PoseidonCurve::SECP256K1,
-            IOPattern::new(vec![]),
-        );
+        let mut poseidon =
+            PoseidonSponge::<Fp, WIDTH>::new(b"test", PoseidonCurve::SECP256K1, io_pattern());
         poseidon.absorb(&input);
         let expected_hash = poseidon.squeeze(1)[0];
 
@@ -161,4 +284,172 @@ mod tests {
         println!("Num constraints: {}", cs.num_constraints.unwrap());
         println!("Num vars: {}", cs.num_vars());
     }
+
+    const NUM_INPUTS: usize = 7;
+    const NUM_OUTPUTS: usize = 5;
+
+    fn multi_permutation_io_pattern() -> IOPattern {
+        IOPattern::new(vec![
+            SpongeOp::Absorb(NUM_INPUTS),
+            SpongeOp::Squeeze(NUM_OUTPUTS),
+        ])
+    }
+
+    fn capacity_circuit<F: FieldGC, const WIDTH: usize, const CAPACITY: usize>(
+        cs: &mut ConstraintSystem<F>,
+    ) {
+        let input = cs.alloc_priv_inputs(NUM_INPUTS);
+
+        let mut poseidon_sponge = PoseidonSpongeChip::<F, WIDTH>::new_with_capacity(
+            b"test",
+            multi_permutation_io_pattern(),
+            PoseidonCurve::SECP256K1,
+            CAPACITY,
+            cs,
+        );
+        poseidon_sponge.absorb(&input);
+
+        for y in poseidon_sponge.squeeze(NUM_OUTPUTS) {
+            cs.expose_public(y);
+        }
+    }
+
+    fn check_capacity<const WIDTH: usize, const CAPACITY: usize>() {
+        let synthesizer =
+            |cs: &mut ConstraintSystem<Fp>| capacity_circuit::<Fp, WIDTH, CAPACITY>(cs);
+
+        let input = (0..NUM_INPUTS)
+            .map(|i| Fp::from(1234567 * (i as u64 + 1)))
+            .collect::<Vec<Fp>>();
+
+        let mut poseidon = poseidon_sponge_native::<Fp, WIDTH>(
+            b"test",
+            multi_permutation_io_pattern(),
+            PoseidonCurve::SECP256K1,
+            CAPACITY,
+        );
+        poseidon.absorb(&input);
+        let expected = poseidon.squeeze(NUM_OUTPUTS);
+
+        let mut cs = ConstraintSystem::new();
+        let witness = cs.gen_witness(synthesizer, &expected, &input);
+
+        cs.set_constraints(&synthesizer);
+        assert!(
+            cs.is_sat(&witness, &expected),
+            "width {} capacity {} mismatch",
+            WIDTH,
+            CAPACITY
+        );
+    }
+
+    #[test]
+    fn test_rate_and_capacity() {
+        // The absorption and the squeezing both span several permutations
+        check_capacity::<3, 1>();
+        check_capacity::<5, 1>();
+        check_capacity::<5, 2>();
+        check_capacity::<9, 4>();
+    }
+
+    // Runs the sponge operations against the IO pattern and returns the result of finish
+    fn run_io_pattern<F: FieldGC>(
+        io_pattern: IOPattern,
+        ops: &[SpongeOp],
+        strict: bool,
+        cs: &mut ConstraintSystem<F>,
+    ) -> Result<(), String> {
+        let mut poseidon_sponge =
+            PoseidonSpongeChip::<F, WIDTH>::new(b"test", io_pattern, PoseidonCurve::SECP256K1, cs);
+        poseidon_sponge.strict = strict;
+
+        let zero = cs.alloc_const(F::ZERO);
+        for op in ops {
+            match op {
+                SpongeOp::Absorb(len) => poseidon_sponge.absorb(&vec![zero; *len]),
+                SpongeOp::Squeeze(len) => {
+                    poseidon_sponge.squeeze(*len);
+                }
+            }
+        }
+
+        poseidon_sponge.finish()
+    }
+
+    #[test]
+    fn test_aggregated_io_pattern() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            // Calls may split the operations of the pattern
+            let io_pattern = IOPattern::new(vec![SpongeOp::Absorb(3), SpongeOp::Squeeze(2)]);
+            let ops = [
+                SpongeOp::Absorb(1),
+                SpongeOp::Absorb(2),
+                SpongeOp::Squeeze(1),
+                SpongeOp::Squeeze(1),
+            ];
+            assert!(run_io_pattern(io_pattern, &ops, false, cs).is_ok());
+
+            // ...and a call may span several operations of the same kind
+            let io_pattern = IOPattern::new(vec![
+                SpongeOp::Absorb(1),
+                SpongeOp::Absorb(2),
+                SpongeOp::Squeeze(1),
+            ]);
+            let ops = [SpongeOp::Absorb(3), SpongeOp::Squeeze(1)];
+            assert!(run_io_pattern(io_pattern, &ops, false, cs).is_ok());
+
+            // An incomplete pattern is reported by finish
+            let io_pattern = IOPattern::new(vec![SpongeOp::Absorb(2), SpongeOp::Squeeze(1)]);
+            let ops = [SpongeOp::Absorb(2)];
+            assert!(run_io_pattern(io_pattern, &ops, false, cs).is_err());
+
+            // ...and so is a deviation from the pattern, unless the sponge is strict
+            let io_pattern = IOPattern::new(vec![SpongeOp::Absorb(2), SpongeOp::Squeeze(1)]);
+            let ops = [SpongeOp::Squeeze(1), SpongeOp::Absorb(2)];
+            assert_eq!(
+                run_io_pattern(io_pattern, &ops, false, cs),
+                Err(
+                    "IO pattern mismatch at operation 0: expected Absorb(2), got Squeeze(1)"
+                        .to_string()
+                )
+            );
+        };
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "IO pattern mismatch at operation 0: expected Absorb(2), got Squeeze(1)"
+    )]
+    fn test_io_pattern_mismatch() {
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let io_pattern = IOPattern::new(vec![SpongeOp::Absorb(2), SpongeOp::Squeeze(1)]);
+            let _ = run_io_pattern(io_pattern, &[SpongeOp::Squeeze(1)], true, cs);
+        };
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "IO pattern mismatch at operation 2: expected the end of the pattern, got Absorb(2)"
+    )]
+    fn test_io_pattern_reuse() {
+        // Reusing a sponge after its pattern is complete is a misuse
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let io_pattern = IOPattern::new(vec![SpongeOp::Absorb(2), SpongeOp::Squeeze(1)]);
+            let ops = [
+                SpongeOp::Absorb(2),
+                SpongeOp::Squeeze(1),
+                SpongeOp::Absorb(2),
+            ];
+            let _ = run_io_pattern(io_pattern, &ops, true, cs);
+        };
+
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+    }
 }
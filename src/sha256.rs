@@ -0,0 +1,276 @@
+use frontend::FieldGC;
+use frontend::Wire;
+
+use crate::bitops::{bit_xor, from_bits, to_bits_le};
+
+// SHA-256 as specified in FIPS 180-4.
+// Inputs and outputs are bit strings in the order SHA-256 consumes them,
+// i.e. bytes in order and the bits of each byte MSB first.
+// Internally, 32-bit words are represented as bits LSB first.
+
+pub const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const BLOCK_BITS: usize = 512;
+// Sums of up to 8 words fit in 35 bits
+const SUM_BITS: usize = 35;
+
+type Word<F> = [Wire<F>; 32];
+
+fn const_word<F: FieldGC>(c: u32, w: Wire<F>) -> Word<F> {
+    let cs = w.cs();
+    let zero = cs.alloc_const(F::ZERO);
+    let one = cs.one();
+
+    let mut out = [zero; 32];
+    for i in 0..32 {
+        if c >> i & 1 == 1 {
+            out[i] = one;
+        }
+    }
+
+    out
+}
+
+fn word_from_be_bits<F: FieldGC>(bits: &[Wire<F>]) -> Word<F> {
+    let mut out = [bits[0]; 32];
+    for i in 0..32 {
+        out[i] = bits[31 - i];
+    }
+
+    out
+}
+
+fn rotate_right_32<F: FieldGC>(a: Word<F>, n: usize) -> Word<F> {
+    let mut out = a;
+    for i in 0..32 {
+        out[i] = a[(i + n) % 32];
+    }
+
+    out
+}
+
+fn shift_right_32<F: FieldGC>(a: Word<F>, n: usize) -> Word<F> {
+    let cs = a[0].cs();
+    let zero = cs.alloc_const(F::ZERO);
+
+    let mut out = [zero; 32];
+    out[..(32 - n)].copy_from_slice(&a[n..]);
+
+    out
+}
+
+fn xor3_32<F: FieldGC>(a: Word<F>, b: Word<F>, c: Word<F>) -> Word<F> {
+    let mut out = a;
+    for i in 0..32 {
+        out[i] = bit_xor(bit_xor(a[i], b[i]), c[i]);
+    }
+
+    out
+}
+
+// (e & f) ^ (!e & g) = e * (f - g) + g
+fn ch_32<F: FieldGC>(e: Word<F>, f: Word<F>, g: Word<F>) -> Word<F> {
+    let cs = e[0].cs();
+
+    let mut out = e;
+    for i in 0..32 {
+        out[i] = cs.deg_2_comb(
+            &[(e[i], F::ONE)],
+            &[(f[i], F::ONE), (g[i], -F::ONE)],
+            &[(g[i], F::ONE)],
+        );
+    }
+
+    out
+}
+
+// (a & b) ^ (a & c) ^ (b & c) = c * (a + b - 2 * a * b) + a * b
+fn maj_32<F: FieldGC>(a: Word<F>, b: Word<F>, c: Word<F>) -> Word<F> {
+    let cs = a[0].cs();
+
+    let mut out = a;
+    for i in 0..32 {
+        let ab = a[i] * b[i];
+        out[i] = cs.deg_2_comb(
+            &[(c[i], F::ONE)],
+            &[(a[i], F::ONE), (b[i], F::ONE), (ab, -F::from(2u32))],
+            &[(ab, F::ONE)],
+        );
+    }
+
+    out
+}
+
+// (sum(words) + c) mod 2^32
+fn add_32<F: FieldGC>(words: &[Word<F>], c: u32) -> Word<F> {
+    let cs = words[0][0].cs();
+
+    let mut sum = cs.alloc_const(F::from(c));
+    for word in words {
+        sum += from_bits(word);
+    }
+
+    let bits = to_bits_le(sum, SUM_BITS);
+    bits[..32].try_into().unwrap()
+}
+
+// The SHA-256 compression function applied to a single 512-bit block
+pub fn sha256_compress<F: FieldGC>(state: [Word<F>; 8], block: &[Wire<F>]) -> [Word<F>; 8] {
+    assert_eq!(block.len(), BLOCK_BITS);
+
+    // Message schedule
+    let mut w = block
+        .chunks(32)
+        .map(word_from_be_bits)
+        .collect::<Vec<Word<F>>>();
+    for t in 16..64 {
+        let s0 = xor3_32(
+            rotate_right_32(w[t - 15], 7),
+            rotate_right_32(w[t - 15], 18),
+            shift_right_32(w[t - 15], 3),
+        );
+        let s1 = xor3_32(
+            rotate_right_32(w[t - 2], 17),
+            rotate_right_32(w[t - 2], 19),
+            shift_right_32(w[t - 2], 10),
+        );
+        w.push(add_32(&[w[t - 16], s0, w[t - 7], s1], 0));
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+
+    for t in 0..64 {
+        let s1 = xor3_32(
+            rotate_right_32(e, 6),
+            rotate_right_32(e, 11),
+            rotate_right_32(e, 25),
+        );
+        let ch = ch_32(e, f, g);
+        let s0 = xor3_32(
+            rotate_right_32(a, 2),
+            rotate_right_32(a, 13),
+            rotate_right_32(a, 22),
+        );
+        let maj = maj_32(a, b, c);
+
+        // temp1 = h + S1 + ch + K[t] + w[t] and temp2 = S0 + maj.
+        // The sums are reduced only once, so the carries of temp1 don't need their own range check.
+        let new_e = add_32(&[d, h, s1, ch, w[t]], K[t]);
+        let new_a = add_32(&[h, s1, ch, w[t], s0, maj], K[t]);
+
+        h = g;
+        g = f;
+        f = e;
+        e = new_e;
+        d = c;
+        c = b;
+        b = a;
+        a = new_a;
+    }
+
+    let working = [a, b, c, d, e, f, g, h];
+    let mut out = state;
+    for i in 0..8 {
+        out[i] = add_32(&[state[i], working[i]], 0);
+    }
+
+    out
+}
+
+// Hashes `input` starting from the given state, after `prefix_bytes` bytes have been absorbed.
+// This allows hashing with a constant prefix (e.g. the BIP-340 tagged hashes)
+// by precomputing its midstate outside of the circuit.
+// `prefix_bytes` must be a multiple of the block size.
+pub fn sha256_from_state<F: FieldGC>(
+    init_state: [u32; 8],
+    prefix_bytes: usize,
+    input: &[Wire<F>],
+) -> Vec<Wire<F>> {
+    assert_eq!((prefix_bytes * 8) % BLOCK_BITS, 0);
+
+    let cs = input[0].cs();
+    let zero = cs.alloc_const(F::ZERO);
+    let one = cs.one();
+
+    // Pad with a one, zeros and the 64-bit length of the message
+    let msg_len = (prefix_bytes * 8 + input.len()) as u64;
+
+    let mut padded = input.to_vec();
+    padded.push(one);
+    while (padded.len() + 64) % BLOCK_BITS != 0 {
+        padded.push(zero);
+    }
+    for i in (0..64).rev() {
+        padded.push(if msg_len >> i & 1 == 1 { one } else { zero });
+    }
+
+    let mut state = init_state.map(|c| const_word(c, one));
+    for block in padded.chunks(BLOCK_BITS) {
+        state = sha256_compress(state, block);
+    }
+
+    state
+        .iter()
+        .flat_map(|word| word.iter().rev().copied())
+        .collect()
+}
+
+pub fn sha256<F: FieldGC>(input: &[Wire<F>]) -> Vec<Wire<F>> {
+    sha256_from_state(SHA256_IV, 0, input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frontend::ConstraintSystem;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    const INPUT: &[u8] = b"abc";
+
+    fn to_be_bits(bytes: &[u8]) -> Vec<Fp> {
+        bytes
+            .iter()
+            .flat_map(|byte| (0..8).rev().map(move |i| Fp::from(byte >> i & 1 == 1)))
+            .collect()
+    }
+
+    #[test]
+    pub fn test_sha256() {
+        let expected =
+            hex::decode("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+                .unwrap();
+
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
+            let input = cs.alloc_priv_inputs(INPUT.len() * 8);
+            let out = sha256(&input);
+
+            for bit in out {
+                cs.expose_public(bit);
+            }
+        };
+
+        let priv_input = to_be_bits(INPUT);
+        let pub_input = to_be_bits(&expected);
+
+        let mut cs = ConstraintSystem::new();
+        let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+
+        cs.set_constraints(&synthesizer);
+        println!("Num constraints: {}", cs.num_constraints.unwrap());
+        assert!(cs.is_sat(&witness, &pub_input));
+    }
+}
@@ -0,0 +1,38 @@
+use frontend::{ConstraintSystem, FieldGC};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+
+// Returns true if the circuit rejects the inputs, either by producing an unsatisfying witness
+// or by failing during witness generation on one of the `assertions`, given by their messages.
+// Any other panic is propagated, so that a crash of the test or the circuit
+// doesn't pass for a rejection.
+pub fn is_rejected<F: FieldGC>(
+    synthesizer: fn(&mut ConstraintSystem<F>),
+    pub_input: &[F],
+    priv_input: &[F],
+    assertions: &[&str],
+) -> bool {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut cs = ConstraintSystem::new();
+        cs.set_constraints(&synthesizer);
+
+        let witness = cs.gen_witness(synthesizer, pub_input, priv_input);
+        !cs.is_sat(&witness, pub_input)
+    }));
+
+    match result {
+        Ok(rejected) => rejected,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().copied())
+                .unwrap_or_default();
+
+            if assertions.iter().any(|a| message.contains(a)) {
+                true
+            } else {
+                resume_unwind(payload)
+            }
+        }
+    }
+}
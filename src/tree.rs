@@ -1,102 +1,365 @@
 use frontend::{ConstraintSystem, FieldGC, Wire};
-use shockwave_plus::{IOPattern, PoseidonCurve, SpongeOp};
+use shockwave_plus::{IOPattern, PoseidonCurve, PoseidonSponge, SpongeOp};
 
 use crate::PoseidonSpongeChip;
 
-const ARTY: usize = 2;
-const SPONGE_WIDTH: usize = ARTY + 1; // The sponge capacity is one, so the width is arity + 1
+// The children of a node are hashed with a sponge of capacity one, so the width is arity + 1.
+// The width is a const generic, so it's selected by matching on the arity.
+// Only the arities in `SupportedArity` are matched, which are all tested.
+macro_rules! with_sponge_width {
+    ($arity:expr, $f:ident($($arg:expr),*)) => {
+        match $arity {
+            2 => $f::<_, 3>($($arg),*),
+            4 => $f::<_, 5>($($arg),*),
+            5 => $f::<_, 6>($($arg),*),
+            8 => $f::<_, 9>($($arg),*),
+            16 => $f::<_, 17>($($arg),*),
+            arity => panic!("unsupported arity {}", arity),
+        }
+    };
+}
 
-pub fn verify_merkle_proof<F: FieldGC>(
-    leaf: Wire<F>,
-    siblings: &[Wire<F>],
-    path_indices: &[Wire<F>],
+// Rejects the arities `with_sponge_width` doesn't support at compile time,
+// when `SupportedArity::<ARITY>::CHECK` is evaluated
+struct SupportedArity<const ARITY: usize>;
+
+impl<const ARITY: usize> SupportedArity<ARITY> {
+    const CHECK: () = assert!(
+        matches!(ARITY, 2 | 4 | 5 | 8 | 16),
+        "the arity must be 2, 4, 5, 8 or 16"
+    );
+}
+
+fn io_pattern(arity: usize) -> IOPattern {
+    IOPattern::new(vec![SpongeOp::Absorb(arity), SpongeOp::Squeeze(1)])
+}
+
+// Each node is hashed with a fresh sponge, as required by its IO pattern
+fn hash_children_with_width<F: FieldGC, const WIDTH: usize>(
+    children: &[Wire<F>],
     cs: &mut ConstraintSystem<F>,
 ) -> Wire<F> {
-    let mut poseidon_sponge = PoseidonSpongeChip::<F, SPONGE_WIDTH>::new(
-        SPONGE_WIDTH.to_string().as_bytes(),
-        IOPattern::new(vec![SpongeOp::Absorb(2), SpongeOp::Squeeze(1)]),
+    let mut poseidon_sponge = PoseidonSpongeChip::<F, WIDTH>::new(
+        WIDTH.to_string().as_bytes(),
+        io_pattern(children.len()),
         PoseidonCurve::SECP256K1,
         cs,
     );
 
+    poseidon_sponge.absorb(children);
+    poseidon_sponge.squeeze(1)[0]
+}
+
+fn hash_children<F: FieldGC>(children: &[Wire<F>], cs: &mut ConstraintSystem<F>) -> Wire<F> {
+    with_sponge_width!(children.len(), hash_children_with_width(children, cs))
+}
+
+fn hash_children_native_with_width<F: FieldGC, const WIDTH: usize>(children: &[F]) -> F {
+    let mut poseidon_sponge = PoseidonSponge::<F, WIDTH>::new(
+        WIDTH.to_string().as_bytes(),
+        PoseidonCurve::SECP256K1,
+        io_pattern(children.len()),
+    );
+
+    poseidon_sponge.absorb(children);
+    poseidon_sponge.squeeze(1)[0]
+}
+
+// Native counterpart of `hash_children`
+fn hash_children_native<F: FieldGC>(children: &[F]) -> F {
+    with_sponge_width!(children.len(), hash_children_native_with_width(children))
+}
+
+// The one-hot encoding of the position of a node among its siblings.
+// Fails if the position isn't less than the arity.
+fn position_flags<F: FieldGC>(
+    position: Wire<F>,
+    arity: usize,
+    cs: &mut ConstraintSystem<F>,
+) -> Vec<Wire<F>> {
+    let one = cs.one();
+
+    let flags = (0..arity)
+        .map(|i| {
+            let i = cs.alloc_const(F::from(i as u64));
+            position.is_equal(i)
+        })
+        .collect::<Vec<Wire<F>>>();
+
+    let num_flags = cs.sum(&flags.iter().map(|b| (*b, true)).collect::<Vec<_>>());
+    cs.assert_equal(num_flags, one, "position is not less than the arity");
+
+    flags
+}
+
+// Places the node at its position among its siblings with conditional swaps.
+// The child j is the node if the position is j, siblings[j - 1] if the position is less than j
+// and siblings[j] otherwise.
+fn insert_node<F: FieldGC>(
+    node: Wire<F>,
+    siblings: &[Wire<F>],
+    is_position: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) -> Vec<Wire<F>> {
+    let arity = is_position.len();
+
+    (0..arity)
+        .map(|j| {
+            // The child j if the node is after it. The last child has no such sibling,
+            // and it's siblings[j - 1] unless the node is at it.
+            let sibling = if j < arity - 1 {
+                siblings[j]
+            } else {
+                siblings[j - 1]
+            };
+
+            // sibling + is_position[j] * (node - sibling)
+            //   + (position < j) * (siblings[j - 1] - siblings[j])
+            let mut deg2_comb_c = vec![(sibling, F::ONE)];
+            if j > 0 && j < arity - 1 {
+                let is_before = is_position[..j]
+                    .iter()
+                    .map(|b| (*b, F::ONE))
+                    .collect::<Vec<(Wire<F>, F)>>();
+                let shift = cs.deg_2_comb(
+                    &is_before,
+                    &[(siblings[j - 1], F::ONE), (siblings[j], -F::ONE)],
+                    &[],
+                );
+                deg2_comb_c.push((shift, F::ONE));
+            }
+
+            cs.deg_2_comb(
+                &[(is_position[j], F::ONE)],
+                &[(node, F::ONE), (sibling, -F::ONE)],
+                &deg2_comb_c,
+            )
+        })
+        .collect()
+}
+
+// Computes the root of an ARITY-ary Merkle tree from the leaf and its proof.
+// ARITY must be 2, 4, 5, 8 or 16.
+// At each level, from the leaf up, `siblings` has the ARITY - 1 siblings of the node in order,
+// and `positions` has the position of the node among the children, in 0..ARITY.
+// The node is placed among its siblings before hashing, so each level costs a single hash.
+pub fn verify_merkle_proof<F: FieldGC, const ARITY: usize>(
+    leaf: Wire<F>,
+    siblings: &[Vec<Wire<F>>],
+    positions: &[Wire<F>],
+    cs: &mut ConstraintSystem<F>,
+) -> Wire<F> {
+    let () = SupportedArity::<ARITY>::CHECK;
+    assert_eq!(siblings.len(), positions.len());
+
     let mut node = leaf;
-    for (sibling, path) in siblings.iter().zip(path_indices.iter()) {
-        poseidon_sponge.absorb(&[node, *sibling]);
-        let left = poseidon_sponge.squeeze(1)[0];
-        poseidon_sponge.absorb(&[*sibling, node]);
-        let right = poseidon_sponge.squeeze(1)[0];
-        node = cs.if_then(path.is_zero(), left).else_then(right);
+    for (level_siblings, position) in siblings.iter().zip(positions.iter()) {
+        assert_eq!(level_siblings.len(), ARITY - 1);
+
+        let is_position = position_flags(*position, ARITY, cs);
+        let children = insert_node(node, level_siblings, &is_position, cs);
+
+        node = hash_children(&children, cs);
     }
 
     node
 }
 
-#[cfg(test)]
-mod tests {
-    use shockwave_plus::PoseidonCurve;
-    use shockwave_plus::PoseidonSponge;
+// An ARITY-ary Merkle tree built outside of the circuit, e.g. to generate proofs.
+// ARITY must be 2, 4, 5, 8 or 16.
+// The leaves are padded with zeros to ARITY^depth.
+#[derive(Clone)]
+pub struct MerkleTree<F: FieldGC, const ARITY: usize> {
+    // The layers of the tree, from the leaves to the root
+    pub layers: Vec<Vec<F>>,
+}
 
-    use super::*;
+impl<F: FieldGC, const ARITY: usize> MerkleTree<F, ARITY> {
+    pub fn new(leaves: &[F], depth: usize) -> Self {
+        let () = SupportedArity::<ARITY>::CHECK;
+        let num_leaves = ARITY.pow(depth as u32);
+        assert!(leaves.len() <= num_leaves, "too many leaves");
 
-    type Fp = frontend::ark_secp256k1::Fq;
-    const TREE_DEPTH: usize = 5;
+        let mut leaves = leaves.to_vec();
+        leaves.resize(num_leaves, F::ZERO);
 
-    #[test]
-    pub fn test_verify_merkle_proof() {
-        let mut poseidon_sponge = PoseidonSponge::<Fp, SPONGE_WIDTH>::new(
-            SPONGE_WIDTH.to_string().as_bytes(),
-            PoseidonCurve::SECP256K1,
-            IOPattern::new(vec![SpongeOp::Absorb(2), SpongeOp::Squeeze(1)]),
-        );
+        let mut layers = vec![leaves];
+        for _ in 0..depth {
+            let layer = layers
+                .last()
+                .unwrap()
+                .chunks(ARITY)
+                .map(hash_children_native)
+                .collect();
+            layers.push(layer);
+        }
 
-        let synthesizer = |cs: &mut ConstraintSystem<Fp>| {
-            let leaf = cs.alloc_priv_input();
-            let siblings = cs.alloc_priv_inputs(TREE_DEPTH);
-            let path_indices = cs.alloc_priv_inputs(TREE_DEPTH);
+        Self { layers }
+    }
 
-            let node = verify_merkle_proof(leaf, &siblings, &path_indices, cs);
-            cs.expose_public(node);
-        };
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
 
-        let siblings = (0..TREE_DEPTH)
-            .map(|i| Fp::from(i as u64))
-            .collect::<Vec<Fp>>();
+    pub fn root(&self) -> F {
+        self.layers[self.depth()][0]
+    }
+
+    // Returns the siblings and the position of the node at each level, from the leaf up,
+    // in the format of `verify_merkle_proof`
+    pub fn proof(&self, leaf_index: usize) -> (Vec<Vec<F>>, Vec<usize>) {
+        assert!(leaf_index < self.layers[0].len(), "leaf index out of range");
+
+        let mut siblings = Vec::with_capacity(self.depth());
+        let mut positions = Vec::with_capacity(self.depth());
 
-        let path_indices = (0..TREE_DEPTH).map(|i| i % 3).collect::<Vec<usize>>();
+        let mut index = leaf_index;
+        for layer in &self.layers[..self.depth()] {
+            let position = index % ARITY;
+            let first_child = index - position;
 
-        // Compute the expected root
+            let mut level_siblings = layer[first_child..(first_child + ARITY)].to_vec();
+            level_siblings.remove(position);
+
+            siblings.push(level_siblings);
+            positions.push(position);
+
+            index /= ARITY;
+        }
+
+        (siblings, positions)
+    }
+}
 
-        let leaf = Fp::from(3u32);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Fp = frontend::ark_secp256k1::Fq;
+
+    // The previous approach, which hashes the children for every position of the node
+    // and selects the hash at its position. Kept to compare the costs.
+    fn verify_merkle_proof_naive<F: FieldGC, const ARITY: usize>(
+        leaf: Wire<F>,
+        siblings: &[Vec<Wire<F>>],
+        positions: &[Wire<F>],
+        cs: &mut ConstraintSystem<F>,
+    ) -> Wire<F> {
         let mut node = leaf;
-        for (sibling, sel) in siblings.iter().zip(path_indices.iter()) {
-            if sel & 1 == 1 {
-                poseidon_sponge.absorb(&[node, *sibling]);
-                node = poseidon_sponge.squeeze(1)[0];
-            } else {
-                poseidon_sponge.absorb(&[*sibling, node]);
-                node = poseidon_sponge.squeeze(1)[0];
+        for (level_siblings, position) in siblings.iter().zip(positions.iter()) {
+            let is_position = position_flags(*position, ARITY, cs);
+
+            let mut terms = Vec::with_capacity(ARITY);
+            for (i, is_position_i) in is_position.iter().enumerate() {
+                let mut children = level_siblings.clone();
+                children.insert(i, node);
+
+                let hash = hash_children(&children, cs);
+                terms.push((*is_position_i * hash, true));
             }
+
+            node = cs.sum(&terms);
         }
 
-        let expected_root = node;
+        node
+    }
+
+    fn merkle_proof_circuit<F: FieldGC, const ARITY: usize, const DEPTH: usize>(
+        cs: &mut ConstraintSystem<F>,
+    ) {
+        let leaf = cs.alloc_priv_input();
+        let siblings = (0..DEPTH)
+            .map(|_| cs.alloc_priv_inputs(ARITY - 1))
+            .collect::<Vec<Vec<Wire<F>>>>();
+        let positions = cs.alloc_priv_inputs(DEPTH);
 
-        // Run the circuit
+        let root = verify_merkle_proof::<F, ARITY>(leaf, &siblings, &positions, cs);
+        cs.expose_public(root);
+    }
+
+    fn naive_merkle_proof_circuit<F: FieldGC, const ARITY: usize, const DEPTH: usize>(
+        cs: &mut ConstraintSystem<F>,
+    ) {
+        let leaf = cs.alloc_priv_input();
+        let siblings = (0..DEPTH)
+            .map(|_| cs.alloc_priv_inputs(ARITY - 1))
+            .collect::<Vec<Vec<Wire<F>>>>();
+        let positions = cs.alloc_priv_inputs(DEPTH);
+
+        let root = verify_merkle_proof_naive::<F, ARITY>(leaf, &siblings, &positions, cs);
+        cs.expose_public(root);
+    }
+
+    fn check_arity<const ARITY: usize, const DEPTH: usize>() {
+        let synthesizer =
+            |cs: &mut ConstraintSystem<Fp>| merkle_proof_circuit::<Fp, ARITY, DEPTH>(cs);
 
         let mut cs = ConstraintSystem::new();
-        let mut priv_input = vec![];
-        priv_input.push(leaf);
-        priv_input.extend_from_slice(&siblings);
-        priv_input.extend_from_slice(
-            &path_indices
-                .iter()
-                .map(|x| Fp::from(*x as u64))
-                .collect::<Vec<Fp>>(),
+        cs.set_constraints(&synthesizer);
+
+        println!(
+            "Num constraints (arity {}, depth {}): {}",
+            ARITY,
+            DEPTH,
+            cs.num_constraints.unwrap()
         );
 
-        let pub_input = [expected_root];
-        let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+        // Leave the last leaf empty, so that the padding is exercised
+        let num_leaves = ARITY.pow(DEPTH as u32) - 1;
+        let leaves = (0..num_leaves)
+            .map(|i| Fp::from(i as u64 * 3 + 1))
+            .collect::<Vec<Fp>>();
+
+        let tree = MerkleTree::<Fp, ARITY>::new(&leaves, DEPTH);
+
+        for leaf_index in [0, num_leaves / 2 + 1, num_leaves - 1] {
+            let (siblings, positions) = tree.proof(leaf_index);
 
+            let mut priv_input = vec![leaves[leaf_index]];
+            priv_input.extend(siblings.concat());
+            priv_input.extend(positions.iter().map(|p| Fp::from(*p as u64)));
+
+            let pub_input = [tree.root()];
+            let witness = cs.gen_witness(synthesizer, &pub_input, &priv_input);
+            assert!(
+                cs.is_sat(&witness, &pub_input),
+                "arity {} leaf {} mismatch",
+                ARITY,
+                leaf_index
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_verify_merkle_proof() {
+        check_arity::<2, 5>();
+        check_arity::<4, 3>();
+        check_arity::<5, 2>();
+        check_arity::<8, 2>();
+        check_arity::<16, 2>();
+    }
+
+    #[test]
+    fn test_single_hash_per_level() {
+        const DEPTH: usize = 20;
+
+        let synthesizer = |cs: &mut ConstraintSystem<Fp>| merkle_proof_circuit::<Fp, 2, DEPTH>(cs);
+        let naive_synthesizer =
+            |cs: &mut ConstraintSystem<Fp>| naive_merkle_proof_circuit::<Fp, 2, DEPTH>(cs);
+
+        let mut cs = ConstraintSystem::new();
         cs.set_constraints(&synthesizer);
-        assert!(cs.is_sat(&witness, &pub_input));
+        let mut naive_cs = ConstraintSystem::new();
+        naive_cs.set_constraints(&naive_synthesizer);
+
+        let num_constraints = cs.num_constraints.unwrap();
+        let naive_num_constraints = naive_cs.num_constraints.unwrap();
+
+        println!("Num constraints (naive): {}", naive_num_constraints);
+        println!("Num constraints: {}", num_constraints);
+
+        // The hashes dominate the cost, so it's roughly halved
+        assert!(num_constraints * 10 < naive_num_constraints * 6);
+        assert!(num_constraints * 10 > naive_num_constraints * 4);
     }
 }